@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::sync;
+
+/// A thread-safe LRU cache of decompressed LZX reset blocks.
+///
+/// Blocks are handed out as shared slices, so readers can keep using a block after it has been
+/// evicted.
+#[derive(Debug)]
+pub(crate) struct BlockCache {
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug)]
+struct CacheState {
+    capacity: usize,
+    /// The cached blocks and their indices, the most recently used one first
+    blocks: VecDeque<(u64, Arc<[u8]>)>,
+}
+
+/// Statistics about the cache of decompressed LZX reset blocks of a file.
+///
+/// See [`ChmFile::block_cache_stats`](crate::ChmFile::block_cache_stats).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of reads served from the cache
+    pub hits: u64,
+    /// The number of reads that had to decompress a block
+    pub misses: u64,
+    /// The maximum number of cached blocks
+    pub capacity: usize,
+    /// The number of currently cached blocks
+    pub len: usize,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                capacity,
+                blocks: VecDeque::with_capacity(capacity),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the block with the given index, calling `decompress` if it isn't cached.
+    ///
+    /// The lock isn't held while decompressing, so concurrent misses on the same block may
+    /// decompress it more than once.
+    pub fn get_or_insert_with<E>(
+        &self,
+        block: u64,
+        decompress: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Arc<[u8]>, E> {
+        {
+            let mut state = self.lock();
+            if let Some(i) = state.blocks.iter().position(|&(index, _)| index == block) {
+                let entry = state.blocks.remove(i).unwrap();
+                let data = Arc::clone(&entry.1);
+                state.blocks.push_front(entry);
+
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(data);
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let data: Arc<[u8]> = decompress()?.into();
+
+        let mut state = self.lock();
+        if state.capacity > 0 && !state.blocks.iter().any(|&(index, _)| index == block) {
+            let keep = state.capacity - 1;
+            state.blocks.truncate(keep);
+            state.blocks.push_front((block, Arc::clone(&data)));
+        }
+
+        Ok(data)
+    }
+
+    /// Changes the maximum number of cached blocks, evicting the least recently used ones if
+    /// there are too many. A capacity of 0 disables the cache.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut state = self.lock();
+        state.capacity = capacity;
+        state.blocks.truncate(capacity);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.lock();
+
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            capacity: state.capacity,
+            len: state.blocks.len(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        sync::lock(&self.state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get(cache: &BlockCache, block: u64) -> Arc<[u8]> {
+        cache
+            .get_or_insert_with(block, || Ok::<_, ()>(vec![block as u8; 4]))
+            .unwrap()
+    }
+
+    #[test]
+    fn it_evicts_the_least_recently_used_block() {
+        let cache = BlockCache::new(2);
+
+        assert_eq!(&*get(&cache, 1), [1; 4]);
+        get(&cache, 2);
+        get(&cache, 1);
+        // evicts block 2
+        get(&cache, 3);
+        get(&cache, 1);
+        get(&cache, 2);
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 4,
+                capacity: 2,
+                len: 2
+            }
+        );
+
+        cache.set_capacity(0);
+        get(&cache, 2);
+        assert_eq!(cache.stats().misses, 5);
+        assert_eq!(cache.stats().len, 0);
+
+        assert!(cache.get_or_insert_with(4, || Err(())).is_err());
+    }
+}
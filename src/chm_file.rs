@@ -1,27 +1,47 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 use std::convert::TryFrom;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+#[cfg(feature = "mmap")]
+use std::path::PathBuf;
 
 use pahs::try_parse;
 use pahs_snafu::ProgressSnafuExt;
-use snafu::{ResultExt, Snafu};
+use snafu::{IntoError, ResultExt, Snafu};
 
+use crate::block_cache::CacheStats;
+use crate::chm_file_head::ReadHead;
+use crate::control_data::{ControlData, ParseControlDataError, CONTROL_DATA_FILE_NAME};
 use crate::directory_listing::listing_chunk::ListingChunkEntry;
+use crate::directory_listing::ParseDirectoryListingError;
+use crate::extract::{self, ExtractError};
+use crate::file_reader::FileReader;
+use crate::link::{resolve_relative, ItsUrl};
+use crate::lzx::{DecompressError, MsCompressedSection};
 use crate::name_list::{NameList, ParseNameListError};
-use crate::{ChmFileHead, Driver, ParseChmFileHeadError, Pos, Progress};
-
-#[derive(Debug)]
-pub enum CompressedContentSection<'a> {
-    Raw(&'a [u8]),
-    Decompressed(Box<[u8]>),
-}
+use crate::recovery::{RecoveredError, SkippedContentSections};
+use crate::reset_table::{ParseResetTableError, ResetTable, RESET_TABLE_FILE_NAME};
+use crate::source::{Region, Source};
+use crate::span_info::{ParseSpanInfoError, SpanInfo, SPAN_INFO_FILE_NAME};
+use crate::system::{ReadSystemError, System};
+use crate::topics::{ReadTopicTableError, TopicTable};
+use crate::tree::DirectoryTree;
+use crate::validation::ValidationPolicy;
+use crate::warning::CompressedFileOutOfBounds;
+#[cfg(feature = "mmap")]
+use crate::OwnedChmFile;
+use crate::{ChmFileHead, Driver, ParseChmFileHeadError, ParseState, ParseWarning, Pos, Progress};
 
 #[derive(Debug)]
 pub struct ChmFile<'a> {
-    file: &'a [u8],
     head: ChmFileHead<'a>,
-    uncompressed_content_section: &'a [u8],
-    compressed_content_section: Option<CompressedContentSection<'a>>,
-    file_entries: HashMap<&'a str, FileEntry>,
+    /// The rest of the file, starting at content section 0
+    uncompressed_content_section: Region<'a>,
+    /// The LZX parameters of the MSCompressed section, if present
+    control_data: Option<ControlData>,
+    compressed_content_section: Option<MsCompressedSection<'a>>,
 }
 
 impl<'a> ChmFile<'a> {
@@ -30,45 +50,220 @@ impl<'a> ChmFile<'a> {
         pos: Pos<'a>,
         file: &'a [u8],
     ) -> Progress<'a, Self, ParseChmFileError> {
-        let (_, head) = try_parse!(ChmFileHead::parse(pd, pos, file).snafu(|_| ParseChmFileHead));
-
-        let file_entry_count: usize = head
-            .directory_listing
-            .entries
-            .iter()
-            .map(|chunk| chunk.entries.len())
-            .sum();
-
-        let mut file_entries: HashMap<_, _> = HashMap::with_capacity(file_entry_count);
+        Self::parse_source(pd, pos, Source::Slice(file))
+    }
 
-        file_entries.extend(head.directory_listing.entries.iter().flat_map(|chunk| {
-            chunk
-                .entries
-                .iter()
-                .cloned()
-                .map(|e| (e.name, FileEntry::from(e)))
-        }));
+    /// Like [`parse`](Self::parse), with `pos` only containing the head of the file, as read by
+    /// [`read_head`](Self::read_head).
+    fn parse_source(
+        pd: &mut Driver,
+        pos: Pos<'a>,
+        source: Source<'a>,
+    ) -> Progress<'a, Self, ParseChmFileError> {
+        let (_, head) =
+            try_parse!(ChmFileHead::parse(pd, pos, source.len()).snafu(|_| ParseChmFileHead));
 
-        let uncompressed_content_section = &file[head.offset_content_section_0..];
+        let uncompressed_content_section =
+            match Region::new(source).slice_from(head.offset_content_section_0 as u64) {
+                Some(section) => section,
+                None => {
+                    return Progress::failure(
+                        pos,
+                        ContentSection0OutOfBounds {
+                            offset: head.offset_content_section_0,
+                        }
+                        .build(),
+                    )
+                }
+            };
 
         Progress::success(
             pos,
             ChmFile {
-                file,
                 head,
-                file_entries,
                 uncompressed_content_section,
+                control_data: None,
                 compressed_content_section: None,
             },
         )
     }
 
+    /// Loads a CHM file from the given data.
+    ///
+    /// Only the header, the header sections and the directory header are parsed up front. The
+    /// directory chunks are parsed when a lookup first needs them, which is a single chunk per
+    /// level of the index, see [`load_lazy`](Self::load_lazy).
+    ///
+    /// The checks that don't need the directory chunks, like the one of the span info against
+    /// the reset table, still fail loading. The checks of the links between the chunks, of the
+    /// order of their entries and of files extending past the end of the MSCompressed section
+    /// need the whole directory, so their warnings are only reported by
+    /// [`load_with_diagnostics`](Self::load_with_diagnostics) and the other loaders parsing the
+    /// directory up front.
     pub fn load(file: &'a [u8]) -> Result<Self, ParseChmFileError> {
-        let pd = &mut Driver::with_state(Default::default());
-        let pos = Pos::new(file);
+        Self::load_lazy(file)
+    }
+
+    /// Like [`load`](Self::load), but parses the whole directory up front and returns the
+    /// warnings about quirks and inconsistencies encountered while parsing the file.
+    pub fn load_with_diagnostics(
+        file: &'a [u8],
+    ) -> Result<(Self, Vec<ParseWarning>), ParseChmFileError> {
+        let mut pd = Driver::with_state(Default::default());
+        let chm_file = Self::load_with_driver(file, &mut pd)?;
+
+        Ok((chm_file, pd.state.warnings))
+    }
+
+    /// Like [`load_with_diagnostics`](Self::load_with_diagnostics), but checks reserved and
+    /// unknown fields according to the given policy.
+    ///
+    /// [`ValidationPolicy::Pedantic`] makes loading fail unless these fields contain the values
+    /// HTML Help Workshop writes.
+    pub fn load_with_policy(
+        file: &'a [u8],
+        validation: ValidationPolicy,
+    ) -> Result<(Self, Vec<ParseWarning>), ParseChmFileError> {
+        let mut pd = Driver::with_state(ParseState {
+            validation,
+            ..Default::default()
+        });
+        let chm_file = Self::load_with_driver(file, &mut pd)?;
+
+        Ok((chm_file, pd.state.warnings))
+    }
+
+    /// Like [`load_with_diagnostics`](Self::load_with_diagnostics), but skips over invalid parts
+    /// of the file instead of failing.
+    ///
+    /// Invalid directory chunks are skipped, as are the entries of a listing chunk following an
+    /// invalid entry. If the additional content sections can't be loaded, only the files in the
+    /// uncompressed content section can be read. The skipped errors are returned together with
+    /// the file. Errors in the file header are not recoverable.
+    pub fn load_recovering(
+        file: &'a [u8],
+    ) -> Result<(Self, Vec<RecoveredError>), ParseChmFileError> {
+        let mut pd = Driver::with_state(ParseState {
+            recover: true,
+            ..Default::default()
+        });
+        let chm_file = Self::load_with_driver(file, &mut pd)?;
+
+        Ok((chm_file, pd.state.recovered_errors))
+    }
+
+    /// Loads a CHM file, only parsing the directory chunks when they are first accessed. This
+    /// is what [`load`](Self::load) does.
+    ///
+    /// This makes opening large files a lot cheaper if only a few files are accessed, but
+    /// changes when problems in the directory are reported:
+    ///
+    /// - Errors in a directory chunk are returned by the lookups and enumerations that need
+    ///   the chunk, e.g. as [`ReadError::ParseDirectory`]. A chunk is only parsed once, so
+    ///   later accesses return the same error.
+    /// - Warnings about a chunk are collected when it's parsed and returned by
+    ///   [`take_deferred_warnings`](Self::take_deferred_warnings).
+    /// - The links between the chunks and the order of their entries aren't checked, and
+    ///   files extending past the end of the MSCompressed section aren't reported. Reading them
+    ///   still fails.
+    pub fn load_lazy(file: &'a [u8]) -> Result<Self, ParseChmFileError> {
+        let pd = &mut Driver::with_state(ParseState {
+            lazy_directory: true,
+            ..Default::default()
+        });
 
-        let mut chm_file = Self::parse(pd, pos, file).finish().1?;
-        chm_file.populate_extra_content_sections(pd)?;
+        Self::load_with_driver(file, pd)
+    }
+
+    /// Like [`load_recovering`](Self::load_recovering), but only parses the directory chunks
+    /// when they are first accessed, like [`load_lazy`](Self::load_lazy).
+    ///
+    /// Lookups and enumerations skip the directory chunks that fail to parse instead of
+    /// returning their errors. These errors are returned by
+    /// [`take_deferred_recovered_errors`](Self::take_deferred_recovered_errors) after the chunks
+    /// have been accessed, the ones returned here are about the rest of the file.
+    pub fn load_lazy_recovering(
+        file: &'a [u8],
+    ) -> Result<(Self, Vec<RecoveredError>), ParseChmFileError> {
+        let mut pd = Driver::with_state(ParseState {
+            lazy_directory: true,
+            recover: true,
+            ..Default::default()
+        });
+        let chm_file = Self::load_with_driver(file, &mut pd)?;
+
+        Ok((chm_file, pd.state.recovered_errors))
+    }
+
+    /// Opens the file at the given path through a memory map.
+    ///
+    /// Like with [`load_lazy`](Self::load_lazy), the directory chunks are only parsed when they
+    /// are first accessed, so the operating system only has to page in the parts of the file
+    /// that are actually used. The map is owned by the returned file, which the parsed names
+    /// borrow from.
+    ///
+    /// The file must not be modified while it is mapped, as this would change the data out
+    /// from under the parser.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<OwnedChmFile, OpenMmapError> {
+        let path = path.as_ref();
+
+        let file = File::open(path).context(MapFile { path })?;
+        // SAFETY: see above, modifications of the file can't be prevented here
+        let map = unsafe { memmap2::Mmap::map(&file) }.context(MapFile { path })?;
+
+        OwnedChmFile::from_mmap(map).context(ParseMappedFile { path })
+    }
+
+    /// Wraps a seekable reader as the source of a file and reads the head of the file from it.
+    ///
+    /// The returned head and source can be passed to [`load_source`](Self::load_source).
+    pub(crate) fn read_head<R: Read + Seek + Send + 'a>(
+        reader: R,
+    ) -> Result<(Cow<'a, [u8]>, Source<'a>), ParseChmFileError> {
+        let source = Source::from_reader(reader)
+            .context(ReadHead)
+            .context(ParseChmFileHead)?;
+        let head = ChmFileHead::read_head(&source).context(ParseChmFileHead)?;
+
+        Ok((head, source))
+    }
+
+    /// Like [`load`](Self::load), with `head` only containing the start of the file up to the
+    /// end of the header sections. Everything else is read from `source` when it's needed.
+    pub(crate) fn load_source(
+        head: &'a [u8],
+        source: Source<'a>,
+    ) -> Result<Self, ParseChmFileError> {
+        let pd = &mut Driver::with_state(ParseState {
+            lazy_directory: true,
+            ..Default::default()
+        });
+
+        Self::load_with_source(head, source, pd)
+    }
+
+    fn load_with_driver(file: &'a [u8], pd: &mut Driver) -> Result<Self, ParseChmFileError> {
+        Self::load_with_source(file, Source::Slice(file), pd)
+    }
+
+    fn load_with_source(
+        head: &'a [u8],
+        source: Source<'a>,
+        pd: &mut Driver,
+    ) -> Result<Self, ParseChmFileError> {
+        let pos = Pos::new(head);
+
+        let mut chm_file = Self::parse_source(pd, pos, source).finish().1?;
+
+        match chm_file.populate_extra_content_sections(pd) {
+            Ok(()) => {}
+            Err(e) if pd.state.recover => pd
+                .state
+                .recovered_errors
+                .push(SkippedContentSections.into_error(e)),
+            Err(e) => return Err(e),
+        }
 
         Ok(chm_file)
     }
@@ -79,52 +274,367 @@ impl<'a> ChmFile<'a> {
     ) -> Result<(), ParseChmFileError> {
         // the section name list contains is inside the first section
         // and contains the names of all other sections
-        let name_list_pos = self
-            .get_pos_for_file("::DataSpace/NameList")
-            .map_err(|e| match e {
-                GetPosForFileError::FileNotFound => MissingContentSectionNameList.build(),
-                GetPosForFileError::FileOutOfBounds => NameListOutOfBounds.build(),
-                _ => unreachable!(),
-            })?;
-
-        let (_, name_list) = NameList::parse(pd, name_list_pos)
+        let name_list_file =
+            self.read_internal_file("::DataSpace/NameList")
+                .map_err(|e| match e {
+                    GetPosForFileError::FileNotFound => MissingContentSectionNameList.build(),
+                    GetPosForFileError::FileOutOfBounds => NameListOutOfBounds.build(),
+                    GetPosForFileError::FileInInvalidContentSection => {
+                        ContentSectionNameListNotInContentSection0.build()
+                    }
+                    e => PopulateContentSections.into_error(e),
+                })?;
+
+        let (_, name_list) = NameList::parse(pd, name_list_file.pos())
             .snafu(|_| ParseNameList)
             .finish();
         let name_list = name_list?;
 
         if name_list.has_ms_compressed_section {
-            let pos = self
-                .get_pos_for_file("::DataSpace/Storage/MSCompressed/Content")
+            let content = self
+                .get_region_for_file("::DataSpace/Storage/MSCompressed/Content")
+                .context(PopulateContentSections)?;
+            let control_data_file = self
+                .read_internal_file(CONTROL_DATA_FILE_NAME)
                 .context(PopulateContentSections)?;
-            self.compressed_content_section = Some(CompressedContentSection::Raw(pos.s));
+            let reset_table_file = self
+                .read_internal_file(RESET_TABLE_FILE_NAME)
+                .context(PopulateContentSections)?;
+
+            let (_, control_data) = ControlData::parse(pd, control_data_file.pos())
+                .snafu(|pos| ParseControlData { offset: pos.offset })
+                .finish();
+            let control_data = control_data?;
+
+            let (_, reset_table) = ResetTable::parse(pd, reset_table_file.pos())
+                .snafu(|pos| ParseResetTable { offset: pos.offset })
+                .finish();
+            let reset_table = reset_table?;
+
+            let section = MsCompressedSection::new(content, &control_data, reset_table)
+                .context(DecompressContentSection)?;
+
+            self.validate_compressed_section_length(pd, &section, reset_table_file.offset)?;
+
+            self.compressed_content_section = Some(section);
+            self.control_data = Some(control_data);
         }
 
         Ok(())
     }
 
-    fn get_pos_for_file(&self, file_name: &str) -> Result<Pos<'a>, GetPosForFileError> {
-        let name_list_entry = self
-            .file_entries
-            .get(file_name)
-            .ok_or_else(|| FileNotFound.build())?;
+    /// Checks the uncompressed length of the MSCompressed section against the span info and the
+    /// files stored in the section, so that truncated sections don't lead to short reads.
+    ///
+    /// Files extending past the end of the section are reported as warnings at the reset table,
+    /// which determines the length. Reading them fails with [`ReadError::OutOfBounds`].
+    fn validate_compressed_section_length(
+        &self,
+        pd: &mut Driver,
+        section: &MsCompressedSection<'_>,
+        reset_table_offset: usize,
+    ) -> Result<(), ParseChmFileError> {
+        match self.read_internal_file(SPAN_INFO_FILE_NAME) {
+            Ok(span_info_file) => {
+                let (_, span_info) = SpanInfo::parse(pd, span_info_file.pos())
+                    .snafu(|pos| ParseSpanInfo { offset: pos.offset })
+                    .finish();
+                let span_info = span_info?;
+
+                if span_info.uncompressed_length != section.len() {
+                    return SpanInfoLengthMismatch {
+                        span_info_length: span_info.uncompressed_length,
+                        reset_table_length: section.len(),
+                    }
+                    .fail();
+                }
+            }
+            // the span info is redundant, so its absence is not an error
+            Err(GetPosForFileError::FileNotFound) => {}
+            Err(e) => return Err(PopulateContentSections.into_error(e)),
+        }
+
+        // checking the files would require parsing the whole directory
+        if pd.state.lazy_directory {
+            return Ok(());
+        }
+
+        // reading these files fails, but the others can still be read. All directory chunks have
+        // been parsed already, the ones that failed are skipped in recovery mode.
+        for entry in self.entries_in_section(1).filter_map(Result::ok) {
+            let end = entry.offset.saturating_add(entry.length);
+            if end > section.len() {
+                pd.state.warnings.push(
+                    CompressedFileOutOfBounds {
+                        offset: reset_table_offset,
+                        name: entry.name,
+                        end,
+                        section_length: section.len(),
+                    }
+                    .build(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the LZX parameters of the MSCompressed section, if the file has one.
+    pub fn control_data(&self) -> Option<&ControlData> {
+        self.control_data.as_ref()
+    }
+
+    /// Reads the metadata of the help file, like its title and default topic, from `/#SYSTEM`.
+    ///
+    /// The file is parsed on every call.
+    pub fn system(&self) -> Result<System, ReadSystemError> {
+        System::read(self)
+    }
+
+    /// Reads the table of topics, which maps the topic numbers used by the binary table of
+    /// contents, the binary index and the full-text search to titles and paths.
+    ///
+    /// The table is parsed on every call.
+    pub fn topic_table(&self) -> Result<TopicTable, ReadTopicTableError> {
+        TopicTable::read(self)
+    }
 
-        if name_list_entry.content_section != 0 {
-            // TODO: implement for compressed section
-            todo!();
+    /// Returns statistics about the cache of decompressed LZX reset blocks, if the file has an
+    /// MSCompressed section.
+    pub fn block_cache_stats(&self) -> Option<CacheStats> {
+        self.compressed_content_section
+            .as_ref()
+            .map(|section| section.cache().stats())
+    }
+
+    /// Changes the maximum number of decompressed LZX reset blocks that are kept in memory.
+    ///
+    /// Defaults to the cache size given in the control data, but at least one block. A capacity
+    /// of 0 disables the cache.
+    pub fn set_block_cache_capacity(&self, blocks: usize) {
+        if let Some(section) = &self.compressed_content_section {
+            section.cache().set_capacity(blocks);
         }
+    }
+
+    /// Returns all entries of the directory listing, in the order they are stored in the file.
+    ///
+    /// Directory chunks that fail to parse are returned as errors in place of their entries,
+    /// unless the file was loaded in recovery mode, where they are skipped.
+    pub fn entries(
+        &self,
+    ) -> impl Iterator<Item = Result<Entry<'a>, ParseDirectoryListingError>> + '_ {
+        self.head
+            .directory_listing
+            .entries()
+            .map(|entry| entry.map(Entry::from))
+    }
+
+    /// Returns all entries stored in the given content section, in the order they are stored in
+    /// the file, along with the errors of directory chunks that fail to parse, see
+    /// [`entries`](Self::entries).
+    pub fn entries_in_section(
+        &self,
+        content_section: u64,
+    ) -> impl Iterator<Item = Result<Entry<'a>, ParseDirectoryListingError>> + '_ {
+        self.entries().filter(
+            move |entry| !matches!(entry, Ok(entry) if entry.content_section != content_section),
+        )
+    }
+
+    /// Returns the warnings about the directory chunks parsed since loading the file or the last
+    /// call.
+    ///
+    /// With [`load`](Self::load) and [`load_lazy`](Self::load_lazy), directory chunks are only
+    /// parsed when they are first accessed, so their warnings can't be returned when loading.
+    pub fn take_deferred_warnings(&self) -> Vec<ParseWarning> {
+        self.head.directory_listing.take_deferred_warnings()
+    }
+
+    /// Returns the errors skipped while parsing directory chunks on first access since loading
+    /// the file or the last call.
+    ///
+    /// Only files loaded with [`load_lazy_recovering`](Self::load_lazy_recovering) skip errors
+    /// on access.
+    pub fn take_deferred_recovered_errors(&self) -> Vec<RecoveredError> {
+        self.head.directory_listing.take_deferred_recovered_errors()
+    }
 
-        usize::try_from(name_list_entry.content_section_offset)
+    /// Looks up the entry with the given name.
+    ///
+    /// Like the Windows CHM viewer, this falls back to an ASCII case-insensitive comparison if
+    /// there is no entry with exactly the given name. Directory chunks that can't be parsed are
+    /// treated as not containing the entry.
+    pub fn find_entry(&self, name: &str) -> Option<Entry<'a>> {
+        self.head
+            .directory_listing
+            .find_ignore_ascii_case(name)
             .ok()
-            .and_then(|offset| self.head.offset_content_section_0.checked_add(offset))
-            .and_then(|start| Some((start, usize::try_from(name_list_entry.content_length).ok()?)))
-            .and_then(|(start, len)| Some((start, start.checked_add(len)?)))
-            .and_then(|(start, end)| Some((start, self.file.get(start..end)?)))
-            .map(|(start, data)| Pos {
-                offset: start,
-                s: data,
-            })
+            .flatten()
+            .map(Entry::from)
+    }
+
+    /// Resolves a link found in the topic `base` (e.g. `/html/topic.htm`) to the entry it
+    /// points to.
+    ///
+    /// Handles relative paths, backslashes, fragments, query strings and percent-encoding.
+    /// Returns `None` if the link doesn't point to an entry of this file.
+    pub fn resolve_link(&self, base: &str, link: &str) -> Option<Entry<'a>> {
+        self.find_entry(&resolve_relative(base, link)?)
+    }
+
+    /// Resolves an `ms-its:`/`mk:@MSITStore:` URL to the entry it points to, if it refers to
+    /// this file.
+    ///
+    /// `file_name` is the name or path this file was loaded from. Returns `None` if the URL
+    /// points into another CHM file or doesn't point to an entry of this file.
+    pub fn resolve_its_url(&self, file_name: &str, url: &ItsUrl) -> Option<Entry<'a>> {
+        if !url.refers_to(file_name) {
+            return None;
+        }
+
+        self.find_entry(url.path.as_ref()?)
+    }
+
+    /// Builds a hierarchical view over the directory listing.
+    ///
+    /// Fails if a directory chunk fails to parse, unless the file was loaded in recovery mode.
+    pub fn directory_tree(&self) -> Result<DirectoryTree<'a>, ParseDirectoryListingError> {
+        Ok(DirectoryTree::new(
+            self.entries().collect::<Result<Vec<_>, _>>()?,
+        ))
+    }
+
+    /// Returns the content of the file with the given name.
+    ///
+    /// Files in the uncompressed content section are borrowed from the CHM file, files in the
+    /// MSCompressed section are decompressed on demand.
+    pub fn read_file(&self, name: &str) -> Result<Cow<'a, [u8]>, ReadError> {
+        let entry = self.get_entry(name)?;
+
+        match entry.content_section {
+            0 => self
+                .get_region_for_entry(entry)
+                .ok_or_else(|| OutOfBounds { name }.build())?
+                .read()
+                .context(ReadData { name }),
+            _ => self
+                .get_compressed_section(name, entry)?
+                .read(entry.offset, entry.length)
+                .map(Cow::Owned)
+                .map_err(|e| match e {
+                    DecompressError::RangeOutOfBounds { .. } => OutOfBounds { name }.build(),
+                    e => Decompress { name }.into_error(e),
+                }),
+        }
+    }
+
+    /// Opens the file with the given name for streaming access.
+    ///
+    /// Unlike [`read_file`](Self::read_file), files in the MSCompressed section are not
+    /// decompressed up front, but one LZX reset block at a time while reading.
+    pub fn open(&self, name: &str) -> Result<FileReader<'_>, ReadError> {
+        let entry = self.get_entry(name)?;
+
+        match entry.content_section {
+            0 => self
+                .get_region_for_entry(entry)
+                .map(FileReader::uncompressed)
+                .ok_or_else(|| OutOfBounds { name }.build()),
+            _ => {
+                let section = self.get_compressed_section(name, entry)?;
+
+                match entry.offset.checked_add(entry.length) {
+                    Some(end) if end <= section.len() => {
+                        Ok(FileReader::compressed(section, entry.offset, entry.length))
+                    }
+                    _ => OutOfBounds { name }.fail(),
+                }
+            }
+        }
+    }
+
+    /// Extracts all files and directories into `dir`, keeping their paths relative to the root
+    /// of the file. Internal files in the `::` namespace are skipped.
+    ///
+    /// The MSCompressed section is split at its LZX reset points, and the resulting ranges are
+    /// decompressed independently of each other, in parallel if the `rayon` feature is enabled.
+    /// Unlike [`read_file`](Self::read_file), this bypasses the block cache.
+    pub fn extract_all(&self, dir: impl AsRef<Path>) -> Result<(), ExtractError> {
+        extract::extract_all(self, self.compressed_content_section.as_ref(), dir.as_ref())
+    }
+
+    fn get_entry(&self, name: &str) -> Result<Entry<'a>, ReadError> {
+        self.head
+            .directory_listing
+            .find(name)
+            .context(ParseDirectory { name })?
+            .map(Entry::from)
+            .ok_or_else(|| NotFound { name }.build())
+    }
+
+    /// Returns the MSCompressed section, if the entry is stored in it.
+    fn get_compressed_section(
+        &self,
+        name: &str,
+        entry: Entry<'a>,
+    ) -> Result<&MsCompressedSection<'a>, ReadError> {
+        match (entry.content_section, &self.compressed_content_section) {
+            (1, Some(section)) => Ok(section),
+            (content_section, _) => InvalidContentSection {
+                name,
+                content_section,
+            }
+            .fail(),
+        }
+    }
+
+    /// Returns the region of a file in the uncompressed content section.
+    fn get_region_for_file(&self, file_name: &str) -> Result<Region<'a>, GetPosForFileError> {
+        let entry = self
+            .head
+            .directory_listing
+            .find(file_name)
+            .context(FileInInvalidDirectoryChunk)?
+            .map(Entry::from)
+            .ok_or_else(|| FileNotFound.build())?;
+
+        if entry.content_section != 0 {
+            return FileInInvalidContentSection.fail();
+        }
+
+        self.get_region_for_entry(entry)
             .ok_or_else(|| FileOutOfBounds.build())
     }
+
+    /// Reads a file in the uncompressed content section, to parse it.
+    fn read_internal_file(&self, file_name: &str) -> Result<InternalFile<'a>, GetPosForFileError> {
+        let region = self.get_region_for_file(file_name)?;
+        let offset = usize::try_from(region.offset()).map_err(|_| FileOutOfBounds.build())?;
+        let data = region.read().context(FileUnreadable)?;
+
+        Ok(InternalFile { offset, data })
+    }
+
+    fn get_region_for_entry(&self, entry: Entry<'a>) -> Option<Region<'a>> {
+        self.uncompressed_content_section
+            .slice(entry.offset, entry.length)
+    }
+}
+
+/// The data of a file in the uncompressed content section.
+struct InternalFile<'a> {
+    offset: usize,
+    data: Cow<'a, [u8]>,
+}
+
+impl InternalFile<'_> {
+    fn pos(&self) -> Pos<'_> {
+        Pos {
+            offset: self.offset,
+            s: &self.data,
+        }
+    }
 }
 
 #[derive(Debug, Snafu)]
@@ -134,6 +644,11 @@ pub enum ParseChmFileError {
         source: ParseChmFileHeadError,
     },
 
+    #[snafu(display("Content section 0 starts at {:#X}, after the end of the file", offset))]
+    ContentSection0OutOfBounds {
+        offset: usize,
+    },
+
     #[snafu(display("Missing content section name list"))]
     MissingContentSectionNameList,
     #[snafu(display("Content section name list not in first context section"))]
@@ -148,34 +663,324 @@ pub enum ParseChmFileError {
     PopulateContentSections {
         source: GetPosForFileError,
     },
+
+    #[snafu(display("Failed to parse the LZX control data at {:#X}:\n{}", offset, source))]
+    ParseControlData {
+        offset: usize,
+        source: ParseControlDataError,
+    },
+
+    #[snafu(display("Failed to parse the LZX reset table at {:#X}:\n{}", offset, source))]
+    ParseResetTable {
+        offset: usize,
+        source: ParseResetTableError,
+    },
+
+    #[snafu(display("Failed to decompress the MSCompressed section:\n{}", source))]
+    DecompressContentSection {
+        source: DecompressError,
+    },
+
+    #[snafu(display("Failed to parse the span info at {:#X}:\n{}", offset, source))]
+    ParseSpanInfo {
+        offset: usize,
+        source: ParseSpanInfoError,
+    },
+
+    #[snafu(display(
+        "The span info length ({:#X}) does not match the reset table length ({:#X})",
+        span_info_length,
+        reset_table_length
+    ))]
+    SpanInfoLengthMismatch {
+        span_info_length: u64,
+        reset_table_length: u64,
+    },
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct FileEntry {
+/// An entry of the directory listing of a CHM file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    pub name: &'a str,
+    /// The content section the data is stored in (0: uncompressed, 1: MSCompressed)
     pub content_section: u64,
-    pub content_section_offset: u64,
-    pub content_length: u64,
+    /// The offset of the data inside of the content section
+    pub offset: u64,
+    pub length: u64,
 }
 
-impl From<ListingChunkEntry<'_>> for FileEntry {
-    fn from(entry: ListingChunkEntry<'_>) -> Self {
-        let ListingChunkEntry {
-            content_section,
-            content_section_offset,
-            content_length,
-            ..
-        } = entry;
+impl<'a> From<&ListingChunkEntry<'a>> for Entry<'a> {
+    fn from(entry: &ListingChunkEntry<'a>) -> Self {
         Self {
-            content_section,
-            content_section_offset,
-            content_length,
+            name: entry.name,
+            content_section: entry.content_section,
+            offset: entry.content_section_offset,
+            length: entry.content_length,
         }
     }
 }
 
+#[cfg(feature = "mmap")]
+#[derive(Debug, Snafu)]
+pub enum OpenMmapError {
+    #[snafu(display("Failed to map the file `{}`:\n{}", path.display(), source))]
+    MapFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to parse the file `{}`:\n{}", path.display(), source))]
+    ParseMappedFile {
+        path: PathBuf,
+        source: ParseChmFileError,
+    },
+}
+
 #[derive(Debug, Snafu)]
 pub enum GetPosForFileError {
     FileNotFound,
     FileOutOfBounds,
     FileInInvalidContentSection,
+    FileInInvalidDirectoryChunk { source: ParseDirectoryListingError },
+    FileUnreadable { source: io::Error },
+}
+
+#[derive(Debug, Snafu)]
+pub enum ReadError {
+    #[snafu(display("The file `{}` does not exist", name))]
+    NotFound { name: String },
+
+    #[snafu(display("Failed to look up the file `{}`:\n{}", name, source))]
+    ParseDirectory {
+        name: String,
+        source: ParseDirectoryListingError,
+    },
+
+    #[snafu(display("The file `{}` is out of the bounds of its content section", name))]
+    OutOfBounds { name: String },
+
+    #[snafu(display("Failed to read the file `{}`:\n{}", name, source))]
+    ReadData { name: String, source: io::Error },
+
+    #[snafu(display(
+        "The file `{}` is in an invalid content section ({})",
+        name,
+        content_section
+    ))]
+    InvalidContentSection { name: String, content_section: u64 },
+
+    #[snafu(display("Failed to decompress the file `{}`:\n{}", name, source))]
+    Decompress {
+        name: String,
+        source: DecompressError,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::header_section_0::ParseHeaderSection0Error;
+    use crate::test_file::{TestFile, DIRECTORY_HEADER_LEN, DIRECTORY_OFFSET, LANGUAGE_ID};
+
+    #[test]
+    fn it_reports_compressed_files_out_of_bounds() {
+        let mut file = TestFile::new();
+        file.add_file("/index.htm", b"<p>index</p>")
+            .add_compressed_file("/a.htm", b"<p>a</p>")
+            .add_entry("/b.htm", 1, 0x10, 0x100);
+        let data = file.build();
+
+        let (chm_file, warnings) = ChmFile::load_with_diagnostics(&data).unwrap();
+        assert!(matches!(
+            &warnings[..],
+            [ParseWarning::CompressedFileOutOfBounds {
+                name,
+                end: 0x110,
+                section_length: 8,
+                ..
+            }] if name == "/b.htm"
+        ));
+
+        assert_eq!(&*chm_file.read_file("/index.htm").unwrap(), b"<p>index</p>");
+        assert_eq!(&*chm_file.read_file("/a.htm").unwrap(), b"<p>a</p>");
+        assert!(matches!(
+            chm_file.read_file("/b.htm"),
+            Err(ReadError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn it_warns_about_inconsistent_headers() {
+        let mut file = TestFile::new();
+        file.add_file("/index.htm", b"<p>index</p>");
+        assert_eq!(ChmFile::load_with_diagnostics(&file.build()).unwrap().1, []);
+
+        file.zero_content_section_0_offset = true;
+        file.directory_language_id = 0x407;
+        let data = file.build();
+
+        let (chm_file, warnings) = ChmFile::load_with_diagnostics(&data).unwrap();
+        assert_eq!(
+            warnings,
+            [
+                ParseWarning::ZeroContentSection0Offset { offset: 0x58 },
+                ParseWarning::LanguageIdMismatch {
+                    offset: DIRECTORY_OFFSET,
+                    header_language_id: LANGUAGE_ID,
+                    directory_language_id: 0x407,
+                },
+            ]
+        );
+        // the files are found relative to the assumed position of the content section 0
+        assert_eq!(&*chm_file.read_file("/index.htm").unwrap(), b"<p>index</p>");
+    }
+
+    #[test]
+    fn it_skips_invalid_content_sections_when_recovering() {
+        let mut file = TestFile::new();
+        file.add_file("/index.htm", b"<p>index</p>")
+            .add_compressed_file("/a.htm", b"<p>a</p>");
+        file.reset_interval = 0;
+        let data = file.build();
+
+        assert!(matches!(
+            ChmFile::load(&data),
+            Err(ParseChmFileError::ParseControlData { .. })
+        ));
+
+        let (chm_file, recovered_errors) = ChmFile::load_recovering(&data).unwrap();
+        assert!(matches!(
+            &recovered_errors[..],
+            [RecoveredError::SkippedContentSections { source }]
+                if matches!(**source, ParseChmFileError::ParseControlData { .. })
+        ));
+
+        assert_eq!(&*chm_file.read_file("/index.htm").unwrap(), b"<p>index</p>");
+        assert!(chm_file.read_file("/a.htm").is_err());
+    }
+
+    #[test]
+    fn it_skips_invalid_directory_chunks_on_access_when_recovering() {
+        let mut file = TestFile::new();
+        file.add_file("/index.htm", b"<p>index</p>");
+        let mut data = file.build();
+        let chunk_offset = DIRECTORY_OFFSET + DIRECTORY_HEADER_LEN;
+        data[chunk_offset + 3] = b'X';
+
+        let (chm_file, recovered_errors) = ChmFile::load_recovering(&data).unwrap();
+        assert!(matches!(
+            &recovered_errors[..],
+            [RecoveredError::SkippedDirectoryChunk { offset, .. }, ..] if *offset == chunk_offset
+        ));
+        assert_eq!(chm_file.take_deferred_recovered_errors().len(), 0);
+
+        let (chm_file, _) = ChmFile::load_lazy_recovering(&data).unwrap();
+        assert_eq!(chm_file.entries().count(), 0);
+        assert!(chm_file.find_entry("/index.htm").is_none());
+        let recovered_errors = chm_file.take_deferred_recovered_errors();
+        assert!(matches!(
+            &recovered_errors[..],
+            [RecoveredError::SkippedDirectoryChunk { offset, .. }] if *offset == chunk_offset
+        ));
+        assert_eq!(chm_file.take_deferred_recovered_errors().len(), 0);
+    }
+
+    #[test]
+    fn it_recovers_from_a_wrong_file_size() {
+        let mut file = TestFile::new();
+        file.add_file("/index.htm", b"<p>index</p>");
+        file.file_size = Some(0x10_0000);
+        let data = file.build();
+
+        assert!(matches!(
+            ChmFile::load(&data),
+            Err(ParseChmFileError::ParseChmFileHead {
+                source: ParseChmFileHeadError::ParseHeaderSection0 { source, .. },
+                ..
+            }) if matches!(
+                *source,
+                ParseHeaderSection0Error::FileSizeMismatch {
+                    parsed: 0x10_0000,
+                    ..
+                }
+            )
+        ));
+
+        let (chm_file, recovered_errors) = ChmFile::load_recovering(&data).unwrap();
+        assert!(matches!(
+            &recovered_errors[..],
+            [RecoveredError::FileSizeMismatch {
+                offset: 0x68,
+                parsed: 0x10_0000,
+                expected,
+            }] if *expected == data.len() as u64
+        ));
+        assert_eq!(&*chm_file.read_file("/index.htm").unwrap(), b"<p>index</p>");
+    }
+
+    #[test]
+    fn it_checks_the_span_info() {
+        let mut file = TestFile::new();
+        file.add_compressed_file("/a.htm", b"<p>a</p>");
+        file.span_info_length = Some(0x20);
+
+        assert!(matches!(
+            ChmFile::load(&file.build()),
+            Err(ParseChmFileError::SpanInfoLengthMismatch {
+                span_info_length: 0x20,
+                reset_table_length: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn it_resolves_links_to_entries() {
+        let mut file = TestFile::new();
+        file.add_entry("/html/", 0, 0, 0)
+            .add_file("/html/Topic.htm", b"<p>topic</p>")
+            .add_file("/html/foo bar.htm", b"<p>foo bar</p>")
+            .add_file("/Images/Foo.GIF", b"GIF89a");
+        let data = file.build();
+        let chm_file = ChmFile::load(&data).unwrap();
+
+        let resolve = |link| {
+            chm_file
+                .resolve_link("/html/topic.htm", link)
+                .map(|entry| entry.name)
+        };
+        assert_eq!(resolve("#anchor"), Some("/html/Topic.htm"));
+        assert_eq!(resolve("?x=1#anchor"), Some("/html/Topic.htm"));
+        assert_eq!(resolve("../images/foo.gif"), Some("/Images/Foo.GIF"));
+        assert_eq!(resolve("foo%20bar.htm#x"), Some("/html/foo bar.htm"));
+        assert_eq!(resolve("..\\HTML\\.\\topic.htm"), Some("/html/Topic.htm"));
+        assert_eq!(resolve("."), Some("/html/"));
+        assert_eq!(resolve("missing.htm"), None);
+        assert_eq!(resolve("http://example.com/"), None);
+
+        assert_eq!(
+            chm_file.find_entry("/html/Topic.htm"),
+            Some(Entry {
+                name: "/html/Topic.htm",
+                content_section: 0,
+                offset: 0,
+                length: 12
+            })
+        );
+        assert_eq!(
+            chm_file
+                .find_entry("/HTML/FOO BAR.HTM")
+                .map(|entry| entry.name),
+            Some("/html/foo bar.htm")
+        );
+        assert_eq!(chm_file.find_entry("/html/foo%20bar.htm"), None);
+
+        let url = ItsUrl::parse("ms-its:C:\\Help\\Test.chm::/html/topic.htm#x").unwrap();
+        assert_eq!(
+            chm_file
+                .resolve_its_url("/home/user/test.chm", &url)
+                .map(|entry| entry.name),
+            Some("/html/Topic.htm")
+        );
+        assert_eq!(chm_file.resolve_its_url("other.chm", &url), None);
+
+        let url = ItsUrl::parse("ms-its:test.chm").unwrap();
+        assert_eq!(chm_file.resolve_its_url("test.chm", &url), None);
+    }
 }
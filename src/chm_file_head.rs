@@ -1,17 +1,23 @@
+use std::borrow::Cow;
 use std::convert::TryFrom;
+use std::io;
 
 use pahs::try_parse;
 use pahs_snafu::ProgressSnafuExt;
-use snafu::Snafu;
+use snafu::{ResultExt, Snafu};
 
 use crate::directory_listing::{DirectoryListing, ParseDirectoryListingError};
 use crate::header::{Header, HeaderSectionTableEntry, ParseHeaderError};
 use crate::header_section_0::{HeaderSection0, ParseHeaderSection0Error};
+use crate::source::{Region, Source};
+use crate::warning::{LanguageIdMismatch, ZeroContentSection0Offset};
 use crate::{Driver, Pos, Progress};
 
+/// The maximum length of the ITSF header, including the header section table
+const MAX_HEADER_LEN: u64 = 0x60;
+
 #[derive(Debug)]
 pub struct ChmFileHead<'a> {
-    file: &'a [u8],
     header: Header,
     header_section_0: HeaderSection0,
     pub(crate) directory_listing: DirectoryListing<'a>,
@@ -19,11 +25,17 @@ pub struct ChmFileHead<'a> {
 }
 
 impl<'a> ChmFileHead<'a> {
+    /// Parses the header, the header sections and the directory.
+    ///
+    /// `pos` has to start at the beginning of the file and contain at least the header
+    /// sections. `file_len` is the length of the whole file.
     pub fn parse(
         pd: &mut Driver,
         pos: Pos<'a>,
-        file: &'a [u8],
+        file_len: u64,
     ) -> Progress<'a, Self, ParseChmFileHeadError> {
+        let head = pos.s;
+
         let (pos_after_header, header) =
             try_parse!(Header::parse(pd, pos).snafu(|pos| ParseHeader { offset: pos.offset }));
 
@@ -32,16 +44,26 @@ impl<'a> ChmFileHead<'a> {
         // the header itself, after the header section table. However, not all files fill this
         // field properly (setting it to 0 instead). In this case we assume the usual position,
         // as this is what the Windows CHM viewer does as well.
-        let offset_content_section_0 = header
-            .offset_content_section_0
-            .map(|o| o as usize)
-            .unwrap_or(pos_after_header.offset);
+        let offset_content_section_0 = match header.offset_content_section_0 {
+            Some(0) => {
+                pd.state.warnings.push(
+                    ZeroContentSection0Offset {
+                        // the offset is the last field of the header
+                        offset: pos_after_header.offset - 8,
+                    }
+                    .build(),
+                );
+                pos_after_header.offset
+            }
+            Some(offset) => offset as usize,
+            None => pos_after_header.offset,
+        };
 
         let (_, (hs0_offset, hs0_data)) = try_parse!(Progress::from_result(
             pos_after_header,
             get_header_section_data(
                 "Header section 0",
-                file,
+                head,
                 &header.header_section_table.header_section_0,
             )
         ));
@@ -51,14 +73,14 @@ impl<'a> ChmFileHead<'a> {
             s: hs0_data,
         };
 
-        let (_, header_section_0) = try_parse!(HeaderSection0::parse(file.len() as u64)(pd, pos)
+        let (_, header_section_0) = try_parse!(HeaderSection0::parse(file_len)(pd, pos)
             .snafu(|_| ParseHeaderSection0 { offset: pos.offset }));
 
         let (_, (dl_offset, dl_data)) = try_parse!(Progress::from_result(
             pos_after_header,
             get_header_section_data(
                 "Directory listing header section",
-                file,
+                head,
                 &header.header_section_table.directory_listing_entry,
             )
         ));
@@ -71,10 +93,21 @@ impl<'a> ChmFileHead<'a> {
         let (pos, directory_listing) = try_parse!(DirectoryListing::parse(pd, pos)
             .snafu(|_| ParseDirectoryListing { offset: pos.offset }));
 
+        let directory_language_id = directory_listing.header.windows_language_id;
+        if directory_language_id != header.language_id {
+            pd.state.warnings.push(
+                LanguageIdMismatch {
+                    offset: dl_offset,
+                    header_language_id: header.language_id,
+                    directory_language_id,
+                }
+                .build(),
+            );
+        }
+
         Progress::success(
             pos,
             ChmFileHead {
-                file,
                 header,
                 header_section_0,
                 directory_listing,
@@ -82,6 +115,33 @@ impl<'a> ChmFileHead<'a> {
             },
         )
     }
+
+    /// Reads the start of the file up to the end of the header sections, which is all that
+    /// [`parse`](Self::parse) needs.
+    pub(crate) fn read_head(source: &Source<'a>) -> Result<Cow<'a, [u8]>, ParseChmFileHeadError> {
+        let file = Region::new(source.clone());
+
+        // the header is parsed again later, this is only for the positions of the sections
+        let header_data = file
+            .slice(0, file.len().min(MAX_HEADER_LEN))
+            .unwrap()
+            .read()
+            .context(ReadHead)?;
+        let pd = &mut Driver::with_state(Default::default());
+        let (_, header) = Header::parse(pd, Pos::new(&header_data))
+            .snafu(|pos| ParseHeader { offset: pos.offset })
+            .finish();
+        let table = header?.header_section_table;
+
+        // out of bounds sections are reported when parsing
+        let end = [table.header_section_0, table.directory_listing_entry]
+            .iter()
+            .map(|section| section.file_offset.saturating_add(section.length))
+            .fold(MAX_HEADER_LEN, u64::max)
+            .min(file.len());
+
+        file.slice(0, end).unwrap().read().context(ReadHead)
+    }
 }
 
 fn get_header_section_data<'a>(
@@ -106,6 +166,9 @@ fn get_header_section_data<'a>(
 #[derive(Debug, Snafu)]
 #[snafu(visibility = "pub")]
 pub enum ParseChmFileHeadError {
+    #[snafu(display("Failed to read the start of the file:\n{}", source))]
+    ReadHead { source: io::Error },
+
     #[snafu(display("The header at {:#X} could not be parsed:\n{}", offset, source))]
     ParseHeader {
         offset: usize,
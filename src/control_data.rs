@@ -0,0 +1,199 @@
+use pahs::slice::num::u32_le;
+use pahs::slice::{tag, NotEnoughDataError};
+use pahs::{try_parse, Recoverable};
+use pahs_snafu::ProgressSnafuExt;
+use snafu::Snafu;
+
+use crate::{Driver, Pos, Progress};
+
+pub(crate) const CONTROL_DATA_FILE_NAME: &str = "::DataSpace/Storage/MSCompressed/ControlData";
+
+/// Size of an uncompressed LZX frame. Version 2 control data specifies its sizes in these units.
+const FRAME_SIZE: u32 = 0x8000;
+
+const MIN_WINDOW_SIZE: u32 = 0x8000;
+const MAX_WINDOW_SIZE: u32 = 0x200_0000;
+
+/// The LZX parameters of the MSCompressed section, parsed from
+/// `::DataSpace/Storage/MSCompressed/ControlData`.
+#[derive(Debug, Clone)]
+pub struct ControlData {
+    pub version: u32,
+    /// Number of bytes of uncompressed data after which the LZX state is reset
+    pub reset_interval: u32,
+    /// Size of the LZX window in bytes
+    pub window_size: u32,
+    /// Suggested size of the cache of decompressed data, see
+    /// [`cache_size_in_bytes`](Self::cache_size_in_bytes)
+    pub cache_size: u32,
+}
+
+impl ControlData {
+    pub fn parse<'a>(pd: &mut Driver, pos: Pos<'a>) -> Progress<'a, Self, ParseControlDataError> {
+        // number of dwords following, not needed as the fields have a fixed layout
+        let (pos, _) = try_parse!(u32_le(pd, pos));
+        let (pos, _) = try_parse!(Self::tag(b"LZXC")(pd, pos));
+        let (pos, version) = try_parse!(u32_le(pd, pos));
+
+        // version 1 specifies sizes in bytes, version 2 in multiples of the frame size
+        let unit = match version {
+            1 => 1,
+            2 => FRAME_SIZE,
+            _ => return Progress::failure(pos, UnsupportedVersion { version }.build()),
+        };
+
+        let (pos, reset_interval) = try_parse!(u32_le(pd, pos));
+        let reset_interval = match reset_interval.checked_mul(unit) {
+            Some(interval) if interval != 0 && interval % FRAME_SIZE == 0 => interval,
+            _ => {
+                return Progress::failure(
+                    pos,
+                    InvalidResetInterval {
+                        reset_interval,
+                        version,
+                    }
+                    .build(),
+                )
+            }
+        };
+
+        let (pos, window_size) = try_parse!(u32_le(pd, pos));
+        let window_size = match window_size.checked_mul(unit) {
+            Some(size)
+                if size.is_power_of_two()
+                    && (MIN_WINDOW_SIZE..=MAX_WINDOW_SIZE).contains(&size) =>
+            {
+                size
+            }
+            _ => {
+                return Progress::failure(
+                    pos,
+                    InvalidWindowSize {
+                        window_size,
+                        version,
+                    }
+                    .build(),
+                )
+            }
+        };
+
+        let (pos, cache_size) = try_parse!(u32_le(pd, pos));
+
+        Progress::success(
+            pos,
+            Self {
+                version,
+                reset_interval,
+                window_size,
+                cache_size,
+            },
+        )
+    }
+
+    /// The suggested size of the cache of decompressed data in bytes.
+    ///
+    /// Like the other sizes, version 2 specifies it in multiples of the frame size. The field is
+    /// only a hint, so it isn't validated when parsing.
+    pub fn cache_size_in_bytes(&self) -> u64 {
+        let unit = if self.version == 2 { FRAME_SIZE } else { 1 };
+        u64::from(self.cache_size) * u64::from(unit)
+    }
+
+    fn tag<'a>(
+        expected: &'static [u8],
+    ) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, &'a [u8], ParseControlDataError> {
+        move |pd, p| {
+            tag(expected)(pd, p).snafu_leaf(|pos| InvalidTag {
+                offset: pos.offset,
+                expected,
+            })
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseControlDataError {
+    #[snafu(display("Not enough data in the input"))]
+    NotEnoughData,
+
+    #[snafu(display("Invalid tag at {:#X}, expected: {:?}", offset, expected))]
+    InvalidTag {
+        offset: usize,
+        expected: &'static [u8],
+    },
+
+    #[snafu(display("Unsupported LZXC version: {}", version))]
+    UnsupportedVersion { version: u32 },
+
+    #[snafu(display(
+        "Invalid reset interval {:#X} for LZXC version {}",
+        reset_interval,
+        version
+    ))]
+    InvalidResetInterval { reset_interval: u32, version: u32 },
+
+    #[snafu(display("Invalid window size {:#X} for LZXC version {}", window_size, version))]
+    InvalidWindowSize { window_size: u32, version: u32 },
+}
+
+impl From<NotEnoughDataError> for ParseControlDataError {
+    fn from(_: NotEnoughDataError) -> Self {
+        NotEnoughData.build()
+    }
+}
+
+impl Recoverable for ParseControlDataError {
+    fn recoverable(&self) -> bool {
+        match self {
+            Self::NotEnoughData => true,
+            Self::InvalidTag { .. } => true,
+            Self::UnsupportedVersion { .. } => false,
+            Self::InvalidResetInterval { .. } => false,
+            Self::InvalidWindowSize { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_parses_version_2() {
+        let input: &[u8] = &[
+            0x06, 0x00, 0x00, 0x00, b'L', b'Z', b'X', b'C', 0x02, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let pos = Pos {
+            offset: 0,
+            s: input,
+        };
+        let pd = &mut Driver::with_state(Default::default());
+
+        let (_, control_data) = ControlData::parse(pd, pos).unwrap();
+        assert_eq!(control_data.version, 2);
+        assert_eq!(control_data.reset_interval, 0x10000);
+        assert_eq!(control_data.window_size, 0x10000);
+        assert_eq!(control_data.cache_size, 2);
+        assert_eq!(control_data.cache_size_in_bytes(), 0x10000);
+    }
+
+    #[test]
+    fn it_fails_on_invalid_window_size() {
+        let input: &[u8] = &[
+            0x06, 0x00, 0x00, 0x00, b'L', b'Z', b'X', b'C', 0x01, 0x00, 0x00, 0x00, 0x00, 0x80,
+            0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let pos = Pos {
+            offset: 0,
+            s: input,
+        };
+        let pd = &mut Driver::with_state(Default::default());
+
+        let (_, err) = ControlData::parse(pd, pos).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseControlDataError::InvalidWindowSize { .. }
+        ));
+    }
+}
@@ -1,71 +1,388 @@
-use listing_chunk::{ListingChunk, ParseListingChunkError};
-use pahs::combinators::count_push_into;
-use pahs::{try_parse, Push, Recoverable};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::mem;
+use std::sync::{Mutex, MutexGuard};
+
+use listing_chunk::{ListingChunk, ListingChunkEntry, ParseListingChunkError};
+use once_cell::sync::OnceCell;
+use pahs::{try_parse, Recoverable};
 use pahs_snafu::ProgressSnafuExt;
-use snafu::Snafu;
+use snafu::{IntoError, Snafu};
 
-use super::{Driver, Pos, Progress};
-use directory_header::{DirectoryHeader, ParseDirectoryHeaderError};
-use index_chunk::{parse_index_chunk, ParseIndexChunkError};
+use super::{Driver, ParseState, Pos, Progress};
+use crate::recovery::{DirectoryTruncated, RecoveredError, SkippedDirectoryChunk};
+use crate::sync;
+use crate::validation::ValidationPolicy;
+use crate::warning::ParseWarning;
+use directory_header::{DirectoryHeader, IndexTreeDepth, ParseDirectoryHeaderError};
+use index_chunk::{parse_index_chunk, IndexChunk, ParseIndexChunkError};
 
+mod consistency;
 mod directory_header;
 mod index_chunk;
 pub mod listing_chunk;
+mod quickref;
 
+#[derive(Debug)]
 enum Chunk<'a> {
     Listing(ListingChunk<'a>),
-    Index,
-}
-
-#[derive(Default)]
-struct OnlyListingChunks<'a>(Vec<ListingChunk<'a>>);
-
-impl<'a> Push<Chunk<'a>> for OnlyListingChunks<'a> {
-    fn push(&mut self, value: Chunk<'a>) {
-        if let Chunk::Listing(chunk) = value {
-            self.0.push(chunk)
-        }
-    }
+    Index(IndexChunk<'a>),
 }
 
+/// The directory of a CHM file.
+///
+/// The directory chunks are parsed when they are first accessed and kept afterwards, unless
+/// [`ParseState::lazy_directory`](crate::ParseState::lazy_directory) is unset, in which case all
+/// of them are parsed up front. Chunks that fail to parse are kept as well, so accessing them
+/// again returns the same error without parsing them again.
+///
+/// Chunks parsed on first access use the validation policy and recovery mode the listing was
+/// parsed with. Their warnings and skipped errors are kept until they are taken with
+/// [`take_deferred_warnings`](Self::take_deferred_warnings) and
+/// [`take_deferred_recovered_errors`](Self::take_deferred_recovered_errors).
 #[derive(Debug)]
 pub struct DirectoryListing<'a> {
     pub header: DirectoryHeader,
-    pub entries: Vec<ListingChunk<'a>>,
+    /// The data following the directory header, starting with the first chunk
+    chunk_data: Pos<'a>,
+    /// All directory chunks that have been parsed so far, or the errors they failed with,
+    /// indexed by their chunk number
+    chunks: Vec<OnceCell<Result<Chunk<'a>, ParseDirectoryListingError>>>,
+    validation: ValidationPolicy,
+    /// Skip chunks that fail to parse instead of returning their errors
+    recover: bool,
+    /// The diagnostics of the chunks parsed on first access
+    deferred: Mutex<Diagnostics>,
+}
+
+#[derive(Debug, Default)]
+struct Diagnostics {
+    warnings: Vec<ParseWarning>,
+    recovered_errors: Vec<RecoveredError>,
 }
 
 impl<'a> DirectoryListing<'a> {
     pub fn parse(pd: &mut Driver, pos: Pos<'a>) -> Progress<'a, Self, ParseDirectoryListingError> {
-        let (pos, header) =
+        let (chunk_data, header) =
             try_parse!(DirectoryHeader::parse(pd, pos).snafu(|_| DirectoryHeaderParse));
 
-        let (pos, OnlyListingChunks(entries)) = try_parse!(count_push_into(
-            header.total_directory_chunk_count as usize,
-            OnlyListingChunks::default,
-            |pd: &mut Driver, pos| pd
-                .alternate(pos)
-                .one(
-                    |pd, pos| ListingChunk::parse(header.directory_chunk_size as usize)(pd, pos)
-                        .snafu(|pos| ListingChunkParse { offset: pos.offset })
-                        .map(Chunk::Listing)
-                )
-                .one(
-                    |pd, pos| parse_index_chunk(header.directory_chunk_size as usize)(pd, pos)
-                        .snafu(|pos| IndexChunkParse { offset: pos.offset })
-                        .map(|_| Chunk::Index)
+        let mut chunk_count = header.total_directory_chunk_count;
+        let chunk_size = header.directory_chunk_size;
+
+        // make sure the chunk count fits the data before allocating space for the chunks
+        match (chunk_count as usize).checked_mul(chunk_size as usize) {
+            Some(len) if len <= chunk_data.s.len() => {}
+            _ if pd.state.recover => {
+                let available = (chunk_data.s.len() / chunk_size as usize) as u32;
+                pd.state.recovered_errors.push(
+                    DirectoryTruncated {
+                        offset: chunk_data.offset,
+                        chunk_count,
+                        available,
+                    }
+                    .build(),
+                );
+                chunk_count = available;
+            }
+            _ => {
+                return Progress::failure(
+                    chunk_data,
+                    ChunksOutOfBounds {
+                        chunk_count,
+                        chunk_size,
+                    }
+                    .build(),
                 )
-                .finish()
-        )(pd, pos));
+            }
+        }
+
+        let listing = Self {
+            header,
+            chunk_data,
+            chunks: (0..chunk_count).map(|_| OnceCell::new()).collect(),
+            validation: pd.state.validation,
+            recover: pd.state.recover,
+            deferred: Mutex::default(),
+        };
+
+        let mut pos = chunk_data;
+
+        if !pd.state.lazy_directory {
+            for (number, cell) in listing.chunks.iter().enumerate() {
+                match listing.parse_chunk(pd, number).finish() {
+                    (end_of_chunk, Ok(chunk)) => {
+                        let _ = cell.set(Ok(chunk));
+                        pos = end_of_chunk;
+                    }
+                    (_, Err(e)) if pd.state.recover => {
+                        // the chunk is skipped when going through all chunks
+                        let offset = listing.chunk_offset(number as u64);
+                        pd.state
+                            .recovered_errors
+                            .push(SkippedDirectoryChunk { offset }.into_error(e.clone()));
+                        let _ = cell.set(Err(e));
+                    }
+                    (failed_pos, Err(e)) => return Progress::failure(failed_pos, e),
+                }
+            }
+
+            listing.check_consistency(pd);
+        }
+
+        pos.success(listing)
+    }
+
+    fn parse_chunk(
+        &self,
+        pd: &mut Driver,
+        number: usize,
+    ) -> Progress<'a, Chunk<'a>, ParseDirectoryListingError> {
+        let chunk_size = self.header.directory_chunk_size as usize;
+        let quickref_density = self.header.quickref_density;
+        let start = number * chunk_size;
+
+        let pos = Pos {
+            offset: self.chunk_offset(number as u64),
+            s: &self.chunk_data.s[start..],
+        };
+
+        pd.alternate(pos)
+            .one(|pd, pos| {
+                ListingChunk::parse(chunk_size, quickref_density)(pd, pos)
+                    .snafu(|pos| ListingChunkParse { offset: pos.offset })
+                    .map(Chunk::Listing)
+            })
+            .one(|pd, pos| {
+                parse_index_chunk(chunk_size, quickref_density)(pd, pos)
+                    .snafu(|pos| IndexChunkParse { offset: pos.offset })
+                    .map(Chunk::Index)
+            })
+            .finish()
+    }
+
+    fn chunk_offset(&self, number: u64) -> usize {
+        self.chunk_data.offset + number as usize * self.header.directory_chunk_size as usize
+    }
+
+    /// Returns the chunk with the given number, parsing it if it hasn't been parsed yet.
+    ///
+    /// Returns `None` if there is no such chunk, or if it failed to parse in recovery mode.
+    fn chunk(&self, number: u64) -> Result<Option<&Chunk<'a>>, ParseDirectoryListingError> {
+        let index = match usize::try_from(number) {
+            Ok(index) if index < self.chunks.len() => index,
+            _ => return Ok(None),
+        };
+
+        match self.chunks[index].get_or_init(|| self.parse_deferred_chunk(index)) {
+            Ok(chunk) => Ok(Some(chunk)),
+            Err(_) if self.recover => Ok(None),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// Parses a chunk on first access, keeping its diagnostics until they are taken.
+    fn parse_deferred_chunk(&self, number: usize) -> Result<Chunk<'a>, ParseDirectoryListingError> {
+        let pd = &mut Driver::with_state(ParseState {
+            recover: self.recover,
+            validation: self.validation,
+            ..Default::default()
+        });
+        let (_, chunk) = self.parse_chunk(pd, number).finish();
+
+        let mut deferred = self.lock_deferred();
+        deferred.warnings.append(&mut pd.state.warnings);
+        deferred
+            .recovered_errors
+            .append(&mut pd.state.recovered_errors);
+
+        if let Err(e) = &chunk {
+            if self.recover {
+                let offset = self.chunk_offset(number as u64);
+                deferred
+                    .recovered_errors
+                    .push(SkippedDirectoryChunk { offset }.into_error(e.clone()));
+            }
+        }
 
-        pos.success(Self { header, entries })
+        chunk
     }
+
+    fn lock_deferred(&self) -> MutexGuard<'_, Diagnostics> {
+        sync::lock(&self.deferred)
+    }
+
+    /// Returns the warnings about the chunks parsed on first access since the last call.
+    pub fn take_deferred_warnings(&self) -> Vec<ParseWarning> {
+        mem::take(&mut self.lock_deferred().warnings)
+    }
+
+    /// Returns the errors skipped in recovery mode while parsing chunks on first access since
+    /// the last call.
+    pub fn take_deferred_recovered_errors(&self) -> Vec<RecoveredError> {
+        mem::take(&mut self.lock_deferred().recovered_errors)
+    }
+
+    /// Returns all listing chunks, ordered by their chunk number.
+    ///
+    /// Chunks that fail to parse are returned as errors, except in recovery mode, where they
+    /// are skipped.
+    pub fn listing_chunks(
+        &self,
+    ) -> impl Iterator<Item = Result<&ListingChunk<'a>, ParseDirectoryListingError>> + '_ {
+        (0..self.chunks.len() as u64).filter_map(move |number| match self.chunk(number) {
+            Ok(Some(Chunk::Listing(chunk))) => Some(Ok(chunk)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Returns all entries of all listing chunks, ordered by their chunk number.
+    ///
+    /// Chunks that fail to parse are returned as errors in place of their entries, except in
+    /// recovery mode, where they are skipped.
+    pub fn entries(
+        &self,
+    ) -> impl Iterator<Item = Result<&ListingChunkEntry<'a>, ParseDirectoryListingError>> + '_ {
+        self.listing_chunks().flat_map(|chunk| {
+            let (entries, error) = match chunk {
+                Ok(chunk) => (&chunk.entries[..], None),
+                Err(e) => (&[][..], Some(Err(e))),
+            };
+
+            entries.iter().map(Ok).chain(error)
+        })
+    }
+
+    /// Looks up the entry with exactly the given name.
+    pub fn find(
+        &self,
+        name: &str,
+    ) -> Result<Option<&ListingChunkEntry<'a>>, ParseDirectoryListingError> {
+        Ok(self
+            .listing_chunk_for(name)?
+            .and_then(|chunk| chunk.find(name)))
+    }
+
+    /// Looks up an entry with the given name, compared ASCII case-insensitively.
+    ///
+    /// An exact match is preferred over other matches.
+    pub fn find_ignore_ascii_case(
+        &self,
+        name: &str,
+    ) -> Result<Option<&ListingChunkEntry<'a>>, ParseDirectoryListingError> {
+        Ok(self
+            .listing_chunk_for(name)?
+            .and_then(|chunk| chunk.find_ignore_ascii_case(name)))
+    }
+
+    /// Returns the only listing chunk that can contain the given name.
+    ///
+    /// Entries are sorted by their ASCII lower case names, both inside of and across chunks, so
+    /// this is the last chunk whose first name doesn't come after the given name. If the file has
+    /// an index, the chunk is looked up by descending the index tree from its root instead of
+    /// going through all chunks.
+    fn listing_chunk_for(
+        &self,
+        name: &str,
+    ) -> Result<Option<&ListingChunk<'a>>, ParseDirectoryListingError> {
+        let (levels, root_index_chunk) = match (
+            self.header.index_tree_depth,
+            self.header.root_index_chunk_number,
+        ) {
+            (IndexTreeDepth::LevelsOfPmgi(levels), Some(root)) => (levels, root),
+            _ => return self.scan_for_listing_chunk(name),
+        };
+
+        let mut number = u64::from(root_index_chunk);
+        // every level takes a chunk of its own, so deeper trees can only be reached by
+        // following a cycle
+        let levels = u64::from(levels).min(self.chunks.len() as u64);
+
+        // every level of the index leads to the next, the lowest one to the listing chunks
+        for _ in 0..levels {
+            let index_chunk = match self.chunk(number)? {
+                Some(Chunk::Index(chunk)) => chunk,
+                // skipped in recovery mode, the listing chunks can still be found without it
+                None if self.recover => return self.scan_for_listing_chunk(name),
+                _ => return Ok(None),
+            };
+
+            number = match last_not_after(&index_chunk.entries, |entry| entry.name, name) {
+                Some(entry) => entry.chunk_starting_with_name,
+                None => return Ok(None),
+            };
+        }
+
+        match self.chunk(number)? {
+            Some(Chunk::Listing(chunk)) => Ok(Some(chunk)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Finds the listing chunk that can contain the given name by going through all listing
+    /// chunks in order.
+    fn scan_for_listing_chunk(
+        &self,
+        name: &str,
+    ) -> Result<Option<&ListingChunk<'a>>, ParseDirectoryListingError> {
+        let after_name = |entry: Option<&ListingChunkEntry<'_>>| match entry {
+            Some(entry) => cmp_names(entry.name, name) == Ordering::Greater,
+            None => false,
+        };
+        let mut found = None;
+
+        for chunk in self.listing_chunks() {
+            let chunk = chunk?;
+            if after_name(chunk.entries.first()) {
+                break;
+            }
+            found = Some(chunk);
+
+            // the following chunks start after the last name of this one, so there's no need to
+            // parse them
+            if after_name(chunk.entries.last()) {
+                break;
+            }
+        }
+
+        Ok(found)
+    }
+}
+
+/// Compares two entry names the way they are sorted in the directory.
+fn cmp_names(a: &str, b: &str) -> Ordering {
+    a.bytes()
+        .map(|b| b.to_ascii_lowercase())
+        .cmp(b.bytes().map(|b| b.to_ascii_lowercase()))
+}
+
+/// Returns the last of the sorted items whose name doesn't come after the given name.
+fn last_not_after<'i, T>(
+    items: &'i [T],
+    item_name: impl Fn(&T) -> &str,
+    name: &str,
+) -> Option<&'i T> {
+    let after = match items
+        .binary_search_by(|item| cmp_names(item_name(item), name).then(Ordering::Less))
+    {
+        Ok(i) | Err(i) => i,
+    };
+
+    items.get(after.checked_sub(1)?)
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseDirectoryListingError {
     #[snafu(display("Failed to parse the directory header:\n{}", source))]
     DirectoryHeaderParse { source: ParseDirectoryHeaderError },
 
+    #[snafu(display(
+        "The {} directory chunks of size {:#X} don't fit the directory",
+        chunk_count,
+        chunk_size
+    ))]
+    ChunksOutOfBounds { chunk_count: u32, chunk_size: u32 },
+
     #[snafu(display("Failed to parse a listing chunk at {:#X}:\n{}", offset, source))]
     ListingChunkParse {
         offset: usize,
@@ -83,8 +400,370 @@ impl Recoverable for ParseDirectoryListingError {
     fn recoverable(&self) -> bool {
         match self {
             Self::DirectoryHeaderParse { source, .. } => source.recoverable(),
+            Self::ChunksOutOfBounds { .. } => false,
             Self::ListingChunkParse { source, .. } => source.recoverable(),
             Self::IndexChunkParse { source, .. } => source.recoverable(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_file::{TestChunk, TestDirectory, DIRECTORY_HEADER_LEN};
+    use crate::{ParseState, ParseWarning, ValidationPolicy};
+
+    const CHUNK_SIZE: usize = 0x80;
+
+    fn directory(index_tree_depth: u32, root_index_chunk: Option<u32>) -> TestDirectory {
+        let mut directory = TestDirectory::new();
+        directory.chunk_size = CHUNK_SIZE;
+        directory.index_tree_depth = index_tree_depth;
+        directory.root_index_chunk = root_index_chunk;
+        directory
+    }
+
+    fn parse(data: &[u8], lazy_directory: bool) -> DirectoryListing<'_> {
+        let pd = &mut Driver::with_state(ParseState {
+            lazy_directory,
+            ..Default::default()
+        });
+        DirectoryListing::parse(pd, Pos::new(data))
+            .finish()
+            .1
+            .unwrap()
+    }
+
+    fn find<'l>(listing: &'l DirectoryListing<'_>, name: &str) -> Option<&'l str> {
+        listing.find(name).unwrap().map(|entry| entry.name)
+    }
+
+    #[test]
+    fn it_looks_up_entries_through_the_index() {
+        let data = directory(2, Some(2))
+            .add_listing_chunk(&["/a.htm", "/B.htm"])
+            .add_listing_chunk(&["/c.htm", "/D.htm"])
+            .add_index_chunk(&[("/a.htm", 0), ("/c.htm", 1)])
+            .build();
+
+        let listing = parse(&data, false);
+
+        assert_eq!(find(&listing, "/B.htm"), Some("/B.htm"));
+        assert_eq!(find(&listing, "/c.htm"), Some("/c.htm"));
+        assert_eq!(find(&listing, "/d.htm"), None);
+        assert_eq!(
+            listing
+                .find_ignore_ascii_case("/d.htm")
+                .unwrap()
+                .unwrap()
+                .name,
+            "/D.htm"
+        );
+        assert_eq!(find(&listing, "/0.htm"), None);
+        assert_eq!(find(&listing, "/e.htm"), None);
+
+        let names: Vec<_> = listing.entries().map(|entry| entry.unwrap().name).collect();
+        assert_eq!(names, ["/a.htm", "/B.htm", "/c.htm", "/D.htm"]);
+    }
+
+    #[test]
+    fn it_parses_chunks_on_first_access() {
+        let data = directory(2, Some(2))
+            .add_listing_chunk(&["/a.htm"])
+            .add_chunk(TestChunk::Invalid)
+            .add_index_chunk(&[("/a.htm", 0), ("/c.htm", 1)])
+            .build();
+
+        let listing = parse(&data, true);
+        assert!(listing.chunks.iter().all(|cell| cell.get().is_none()));
+
+        assert_eq!(find(&listing, "/a.htm"), Some("/a.htm"));
+        assert!(listing.chunks[0].get().is_some());
+        assert!(listing.chunks[1].get().is_none());
+
+        assert!(matches!(
+            listing.find("/c.htm"),
+            Err(ParseDirectoryListingError::IndexChunkParse { offset, .. })
+                if offset == DIRECTORY_HEADER_LEN + CHUNK_SIZE
+        ));
+    }
+
+    #[test]
+    fn it_rejects_chunks_smaller_than_a_chunk_header() {
+        let mut directory = directory(1, None);
+        directory.chunk_size = 0;
+        directory.chunk_count = Some(u32::MAX);
+        let data = directory.build();
+
+        for &recover in &[false, true] {
+            let pd = &mut Driver::with_state(ParseState {
+                recover,
+                ..Default::default()
+            });
+            assert!(matches!(
+                DirectoryListing::parse(pd, Pos::new(&data)).finish().1,
+                Err(ParseDirectoryListingError::DirectoryHeaderParse {
+                    source: ParseDirectoryHeaderError::ChunkSizeTooSmall { chunk_size: 0 }
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn it_descends_multiple_index_levels() {
+        let data = directory(3, Some(5))
+            .add_listing_chunk(&["/a.htm", "/b.htm"])
+            .add_listing_chunk(&["/c.htm", "/d.htm"])
+            .add_listing_chunk(&["/e.htm", "/f.htm"])
+            .add_index_chunk(&[("/a.htm", 0), ("/c.htm", 1)])
+            .add_index_chunk(&[("/e.htm", 2)])
+            .add_index_chunk(&[("/a.htm", 3), ("/e.htm", 4)])
+            .build();
+
+        let listing = parse(&data, true);
+        assert_eq!(
+            listing.header.index_tree_depth,
+            IndexTreeDepth::LevelsOfPmgi(2)
+        );
+
+        // only the chunks on the path to the entry are parsed
+        assert_eq!(find(&listing, "/f.htm"), Some("/f.htm"));
+        let parsed: Vec<_> = (0..6)
+            .filter(|&i| listing.chunks[i].get().is_some())
+            .collect();
+        assert_eq!(parsed, [2, 4, 5]);
+
+        for name in &["/a.htm", "/b.htm", "/c.htm", "/d.htm", "/e.htm"] {
+            assert_eq!(find(&listing, name), Some(*name));
+        }
+        assert_eq!(find(&listing, "/g.htm"), None);
+    }
+
+    #[test]
+    fn it_stops_descending_index_cycles() {
+        let data = directory(u32::MAX, Some(1))
+            .add_listing_chunk(&["/a.htm"])
+            // refers to itself instead of the listing chunk
+            .add_index_chunk(&[("/a.htm", 1)])
+            .build();
+
+        for &lazy_directory in &[false, true] {
+            let listing = parse(&data, lazy_directory);
+            assert_eq!(find(&listing, "/a.htm"), None);
+        }
+    }
+
+    #[test]
+    fn it_checks_the_chunk_links() {
+        let mut directory = directory(2, Some(3));
+        directory
+            .add_listing_chunk(&["/a.htm", "/b.htm"])
+            // sorted after the entries of the next chunk
+            .add_listing_chunk(&["/e.htm"])
+            .add_listing_chunk(&["/c.htm", "/d.htm"])
+            .add_index_chunk(&[("/a.htm", 0), ("/c.htm", 1), ("/e.htm", 5)]);
+        let mut data = directory.build();
+        // chunk 2 links back to chunk 1 instead of ending the chain
+        let next_link = directory.chunk_offset(2) + 0x10;
+        data[next_link..next_link + 4].copy_from_slice(&1u32.to_le_bytes());
+
+        let pd = &mut Driver::with_state(Default::default());
+        DirectoryListing::parse(pd, Pos::new(&data))
+            .finish()
+            .1
+            .unwrap();
+
+        let chunk_offset = |number| directory.chunk_offset(number);
+        assert_eq!(
+            pd.state.warnings,
+            [
+                ParseWarning::EntriesNotSorted {
+                    offset: chunk_offset(2)
+                },
+                ParseWarning::ChainCycle {
+                    offset: chunk_offset(1)
+                },
+                ParseWarning::IndexKeyMismatch {
+                    offset: chunk_offset(3)
+                },
+                ParseWarning::IndexKeyChunkMissing {
+                    offset: chunk_offset(3)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_skips_invalid_chunks_and_entries_when_recovering() {
+        let mut data = directory(1, None)
+            .add_listing_chunk(&["/a.htm", "/b.htm", "/c.htm"])
+            .add_chunk(TestChunk::Invalid)
+            .add_listing_chunk(&["/d.htm"])
+            .build();
+        // invalid UTF-8 in the name of the second entry
+        let second_entry = DIRECTORY_HEADER_LEN + 0x14 + 10;
+        data[second_entry + 2] = 0xFF;
+
+        let pd = &mut Driver::with_state(Default::default());
+        assert!(DirectoryListing::parse(pd, Pos::new(&data))
+            .finish()
+            .1
+            .is_err());
+
+        let pd = &mut Driver::with_state(ParseState {
+            recover: true,
+            ..Default::default()
+        });
+        let listing = DirectoryListing::parse(pd, Pos::new(&data))
+            .finish()
+            .1
+            .unwrap();
+
+        let names: Vec<_> = listing.entries().map(|entry| entry.unwrap().name).collect();
+        assert_eq!(names, ["/a.htm", "/d.htm"]);
+        assert_eq!(find(&listing, "/d.htm"), Some("/d.htm"));
+
+        let offsets: Vec<_> = pd
+            .state
+            .recovered_errors
+            .iter()
+            .map(|e| e.offset())
+            .collect();
+        assert_eq!(
+            offsets,
+            [Some(second_entry), Some(DIRECTORY_HEADER_LEN + CHUNK_SIZE)]
+        );
+    }
+
+    #[test]
+    fn it_checks_reserved_fields_according_to_the_policy() {
+        let mut data = directory(1, None).add_listing_chunk(&["/a.htm"]).build();
+        // the last of the `-1` markers
+        let marker = DIRECTORY_HEADER_LEN - 4;
+        data[marker..marker + 4].copy_from_slice(&[0; 4]);
+        data[DIRECTORY_HEADER_LEN + 8] = 0x0D;
+
+        fn parse(
+            data: &[u8],
+            validation: ValidationPolicy,
+        ) -> (Result<usize, ParseDirectoryListingError>, Vec<ParseWarning>) {
+            let pd = &mut Driver::with_state(ParseState {
+                validation,
+                ..Default::default()
+            });
+            let (_, result) = DirectoryListing::parse(pd, Pos::new(data)).finish();
+            (
+                result.map(|listing| listing.entries().count()),
+                pd.state.warnings.clone(),
+            )
+        }
+
+        assert!(matches!(
+            parse(&data, ValidationPolicy::Default).0,
+            Err(ParseDirectoryListingError::DirectoryHeaderParse { .. })
+        ));
+
+        let (result, warnings) = parse(&data, ValidationPolicy::Lenient);
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(warnings, []);
+
+        data[marker..marker + 4].copy_from_slice(&[0xFF; 4]);
+
+        let (result, warnings) = parse(&data, ValidationPolicy::Default);
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(
+            warnings,
+            [ParseWarning::UnexpectedReservedValue {
+                offset: DIRECTORY_HEADER_LEN + 8,
+                field: "reserved",
+                value: 0x0D,
+                expected: 0
+            }]
+        );
+
+        assert!(matches!(
+            parse(&data, ValidationPolicy::Pedantic).0,
+            Err(ParseDirectoryListingError::ListingChunkParse { .. })
+        ));
+    }
+
+    #[test]
+    fn it_parses_chunks_on_access_like_up_front() {
+        let mut data = directory(1, None)
+            .add_listing_chunk(&["/a.htm", "/b.htm"])
+            .add_chunk(TestChunk::Invalid)
+            .add_listing_chunk(&["/c.htm"])
+            .build();
+        data[DIRECTORY_HEADER_LEN + 8] = 0x0D;
+
+        let lazy_driver = |validation, recover| {
+            Driver::with_state(ParseState {
+                lazy_directory: true,
+                validation,
+                recover,
+                ..Default::default()
+            })
+        };
+
+        let pd = &mut lazy_driver(ValidationPolicy::Default, false);
+        let listing = DirectoryListing::parse(pd, Pos::new(&data))
+            .finish()
+            .1
+            .unwrap();
+        assert_eq!(find(&listing, "/a.htm"), Some("/a.htm"));
+        assert_eq!(pd.state.warnings, []);
+        assert_eq!(
+            listing.take_deferred_warnings(),
+            [ParseWarning::UnexpectedReservedValue {
+                offset: DIRECTORY_HEADER_LEN + 8,
+                field: "reserved",
+                value: 0x0D,
+                expected: 0
+            }]
+        );
+        assert_eq!(find(&listing, "/a.htm"), Some("/a.htm"));
+        assert_eq!(listing.take_deferred_warnings(), []);
+
+        // the invalid chunk is only parsed once, and its error is returned on every access
+        for _ in 0..2 {
+            let entries: Vec<_> = listing.entries().collect();
+            assert_eq!(entries.len(), 4);
+            assert!(matches!(
+                entries[2],
+                Err(ParseDirectoryListingError::IndexChunkParse { offset, .. })
+                    if offset == DIRECTORY_HEADER_LEN + CHUNK_SIZE
+            ));
+            assert!(listing.find("/c.htm").is_err());
+        }
+
+        let pd = &mut lazy_driver(ValidationPolicy::Pedantic, false);
+        let listing = DirectoryListing::parse(pd, Pos::new(&data))
+            .finish()
+            .1
+            .unwrap();
+        assert!(matches!(
+            listing.find("/a.htm"),
+            Err(ParseDirectoryListingError::ListingChunkParse { .. })
+        ));
+
+        let pd = &mut lazy_driver(ValidationPolicy::Lenient, true);
+        let listing = DirectoryListing::parse(pd, Pos::new(&data))
+            .finish()
+            .1
+            .unwrap();
+        for _ in 0..2 {
+            let names: Vec<_> = listing.entries().map(|entry| entry.unwrap().name).collect();
+            assert_eq!(names, ["/a.htm", "/b.htm", "/c.htm"]);
+        }
+        assert_eq!(find(&listing, "/c.htm"), Some("/c.htm"));
+        assert_eq!(listing.take_deferred_warnings(), []);
+
+        let offsets: Vec<_> = listing
+            .take_deferred_recovered_errors()
+            .iter()
+            .map(|e| e.offset())
+            .collect();
+        assert_eq!(offsets, [Some(DIRECTORY_HEADER_LEN + CHUNK_SIZE)]);
+        assert!(pd.state.recovered_errors.is_empty());
+    }
+}
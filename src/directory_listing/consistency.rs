@@ -0,0 +1,131 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use super::listing_chunk::ListingChunk;
+use super::{cmp_names, Chunk, DirectoryListing};
+use crate::warning::{
+    ChainBackLinkMismatch, ChainCycle, ChainEndMismatch, ChainLinksToInvalidChunk, ChunkNotInChain,
+    EntriesNotSorted, IndexKeyChunkMissing, IndexKeyMismatch,
+};
+use crate::{Driver, ParseWarning};
+
+impl<'a> DirectoryListing<'a> {
+    /// Checks the links between the parsed directory chunks and the order of their entries.
+    ///
+    /// Every problem is reported as a warning at the offset of the affected chunk.
+    pub(super) fn check_consistency(&self, pd: &mut Driver) {
+        self.check_listing_chain(pd);
+        self.check_index_keys(pd);
+    }
+
+    /// Walks the doubly linked list of PMGL chunks, starting at the first one named in the
+    /// header, and checks that the entry names are sorted across the whole list.
+    fn check_listing_chain(&self, pd: &mut Driver) {
+        let mut warn = |number: u32, warning: fn(usize) -> ParseWarning| {
+            let offset = self.chunk_offset(u64::from(number));
+            pd.state.warnings.push(warning(offset));
+        };
+
+        let mut visited = HashSet::new();
+        let mut previous = None;
+        let mut previous_name: Option<&str> = None;
+        let mut number = self.header.first_pmgl_chunk_number;
+
+        loop {
+            let chunk = match self.parsed_listing_chunk(number) {
+                Some(chunk) => chunk,
+                None => {
+                    warn(number, |offset| ChainLinksToInvalidChunk { offset }.build());
+                    break;
+                }
+            };
+
+            if !visited.insert(number) {
+                warn(number, |offset| ChainCycle { offset }.build());
+                break;
+            }
+
+            if chunk.chunk_index_before != previous {
+                warn(number, |offset| ChainBackLinkMismatch { offset }.build());
+            }
+
+            let mut sorted = true;
+            for entry in &chunk.entries {
+                if let Some(previous_name) = previous_name {
+                    sorted &= cmp_names(previous_name, entry.name) != Ordering::Greater;
+                }
+                previous_name = Some(entry.name);
+            }
+            if !sorted {
+                warn(number, |offset| EntriesNotSorted { offset }.build());
+            }
+
+            previous = Some(number);
+
+            match chunk.chunk_index_after {
+                Some(next) => number = next,
+                None => {
+                    if number != self.header.last_pmgl_chunk_number {
+                        warn(number, |offset| ChainEndMismatch { offset }.build());
+                    }
+                    break;
+                }
+            }
+        }
+
+        for (number, cell) in (0..).zip(&self.chunks) {
+            if let Some(Ok(Chunk::Listing(_))) = cell.get() {
+                if !visited.contains(&number) {
+                    warn(number, |offset| ChunkNotInChain { offset }.build());
+                }
+            }
+        }
+    }
+
+    /// Checks that the keys of all PMGI chunks match the first name of the chunk they refer to.
+    fn check_index_keys(&self, pd: &mut Driver) {
+        for (number, cell) in (0..).zip(&self.chunks) {
+            let index_chunk = match cell.get() {
+                Some(Ok(Chunk::Index(chunk))) => chunk,
+                _ => continue,
+            };
+
+            for entry in &index_chunk.entries {
+                let target = usize::try_from(entry.chunk_starting_with_name)
+                    .ok()
+                    .and_then(|number| self.chunks.get(number)?.get()?.as_ref().ok());
+
+                let first_name = match target {
+                    Some(Chunk::Listing(chunk)) => chunk.entries.first().map(|entry| entry.name),
+                    Some(Chunk::Index(chunk)) => chunk.entries.first().map(|entry| entry.name),
+                    None => {
+                        pd.state.warnings.push(
+                            IndexKeyChunkMissing {
+                                offset: self.chunk_offset(number),
+                            }
+                            .build(),
+                        );
+                        continue;
+                    }
+                };
+
+                if first_name != Some(entry.name) {
+                    pd.state.warnings.push(
+                        IndexKeyMismatch {
+                            offset: self.chunk_offset(number),
+                        }
+                        .build(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn parsed_listing_chunk(&self, number: u32) -> Option<&ListingChunk<'a>> {
+        match self.chunks.get(number as usize)?.get()?.as_ref().ok()? {
+            Chunk::Listing(chunk) => Some(chunk),
+            Chunk::Index(_) => None,
+        }
+    }
+}
@@ -3,14 +3,18 @@ use pahs::slice::num::u32_le;
 use pahs::slice::{tag, NotEnoughDataError};
 use pahs::{sequence, Recoverable};
 use pahs_snafu::ProgressSnafuExt;
-use snafu::Snafu;
+use snafu::{IntoError, Snafu};
 use uuid::Uuid;
 
 use crate::uuid::{parse_exact_uuid, ParseExactUuidError};
+use crate::validation::{reserved_u32, UnexpectedReservedValueError, ValidationPolicy};
 use crate::{Driver, Pos, Progress};
 
 const DIRECTORY_HEADER_GUID: Uuid = Uuid::from_bytes(hex!("6A92025D2E21D0119DF900A0C922E6EC"));
 
+/// The length of the header of a listing chunk, which every directory has at least one of.
+const MIN_CHUNK_SIZE: u32 = 0x14;
+
 #[derive(Debug)]
 pub struct DirectoryHeader {
     pub version: u32,
@@ -30,8 +34,6 @@ impl DirectoryHeader {
         pd: &mut Driver,
         pos: Pos<'a>,
     ) -> Progress<'a, Self, ParseDirectoryHeaderError> {
-        const MINUS_ONE: &[u8; 4] = &(-1i32).to_le_bytes();
-
         sequence!(
             pd,
             pos,
@@ -40,18 +42,27 @@ impl DirectoryHeader {
                 let version = u32_le;
                 let directory_header_length = u32_le;
 
-                // unknown
-                u32_le;
+                reserved_u32::<ParseDirectoryHeaderError>("unknown_dword", 0x0A);
 
-                let directory_chunk_size = u32_le;
+                let directory_chunk_size = |pd, p| {
+                    u32_le(pd, p)
+                        .snafu_leaf(|_| NotEnoughData)
+                        .and_then(p, |chunk_size| {
+                            if chunk_size >= MIN_CHUNK_SIZE {
+                                Ok(chunk_size)
+                            } else {
+                                Err(ChunkSizeTooSmall { chunk_size }.build())
+                            }
+                        })
+                };
                 let quickref_density = u32_le;
                 let index_tree_depth = |pd, p| {
                     u32_le(pd, p)
                         .snafu_leaf(|_| NotEnoughData)
                         .and_then(p, |value| match value {
+                            0 => Err(UnknownIndexTreeDepth.build()),
                             1 => Ok(IndexTreeDepth::NoIndex),
-                            2 => Ok(IndexTreeDepth::OneLevelOfPmgi),
-                            _ => Err(UnknownIndexTreeDepth.build()),
+                            depth => Ok(IndexTreeDepth::LevelsOfPmgi(depth - 1)),
                         })
                 };
 
@@ -61,7 +72,7 @@ impl DirectoryHeader {
                 let first_pmgl_chunk_number = u32_le;
                 let last_pmgl_chunk_number = u32_le;
 
-                Self::tag(MINUS_ONE);
+                Self::minus_one();
 
                 let directory_chunk_count = u32_le;
                 let windows_language_id = u32_le;
@@ -85,9 +96,9 @@ impl DirectoryHeader {
                 };
 
                 // unknown
-                Self::tag(MINUS_ONE);
-                Self::tag(MINUS_ONE);
-                Self::tag(MINUS_ONE);
+                Self::minus_one();
+                Self::minus_one();
+                Self::minus_one();
             },
             Self {
                 version,
@@ -104,6 +115,17 @@ impl DirectoryHeader {
         )
     }
 
+    /// A `-1` marker, which is only checked if the validation policy isn't lenient
+    fn minus_one<'a>(
+    ) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, &'a [u8], ParseDirectoryHeaderError> {
+        const MINUS_ONE: &[u8; 4] = &(-1i32).to_le_bytes();
+
+        move |pd, p| match pd.state.validation {
+            ValidationPolicy::Lenient => p.take(4).snafu_leaf(|_| NotEnoughData),
+            ValidationPolicy::Default | ValidationPolicy::Pedantic => Self::tag(MINUS_ONE)(pd, p),
+        }
+    }
+
     fn tag<'a>(
         expected: &'static [u8],
     ) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, &'a [u8], ParseDirectoryHeaderError> {
@@ -116,7 +138,7 @@ impl DirectoryHeader {
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseDirectoryHeaderError {
     #[snafu(display("Not enough data in the input"))]
     NotEnoughData,
@@ -124,6 +146,12 @@ pub enum ParseDirectoryHeaderError {
     #[snafu(display("Unknown specified index tree depth"))]
     UnknownIndexTreeDepth,
 
+    #[snafu(display(
+        "The directory chunk size {:#X} is too small for a chunk header",
+        chunk_size
+    ))]
+    ChunkSizeTooSmall { chunk_size: u32 },
+
     #[snafu(display("Invalid tag at {:#X}, expected: {:?}", offset, expected))]
     InvalidTag {
         offset: usize,
@@ -135,6 +163,11 @@ pub enum ParseDirectoryHeaderError {
 
     #[snafu(display("The two fields specifying the directory header length do not match (first: {:#X}, second: {:#X})", first, second))]
     DirectoryHeaderLengthsDoNotMatch { first: u32, second: u32 },
+
+    #[snafu(display("Failed the pedantic validation:\n{}", source))]
+    UnexpectedReservedValue {
+        source: UnexpectedReservedValueError,
+    },
 }
 
 impl From<NotEnoughDataError> for ParseDirectoryHeaderError {
@@ -143,6 +176,12 @@ impl From<NotEnoughDataError> for ParseDirectoryHeaderError {
     }
 }
 
+impl From<UnexpectedReservedValueError> for ParseDirectoryHeaderError {
+    fn from(e: UnexpectedReservedValueError) -> Self {
+        UnexpectedReservedValue.into_error(e)
+    }
+}
+
 impl Recoverable for ParseDirectoryHeaderError {
     fn recoverable(&self) -> bool {
         match self {
@@ -151,13 +190,16 @@ impl Recoverable for ParseDirectoryHeaderError {
             Self::InvalidTag { .. } => true,
 
             Self::UnknownIndexTreeDepth => false,
+            Self::ChunkSizeTooSmall { .. } => false,
             Self::DirectoryHeaderLengthsDoNotMatch { .. } => false,
+            Self::UnexpectedReservedValue { .. } => false,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IndexTreeDepth {
     NoIndex,
-    OneLevelOfPmgi,
+    /// The number of levels of PMGI chunks above the PMGL chunks, at least 1
+    LevelsOfPmgi(u32),
 }
@@ -1,19 +1,30 @@
 use std::convert::TryInto;
 use std::str::Utf8Error;
 
-use pahs::combinators::zero_or_more_push_into;
+use pahs::combinators::zero_or_more;
 use pahs::slice::num::u32_le;
 use pahs::slice::NotEnoughDataError;
 use pahs::{sequence, try_parse, Recoverable};
 use pahs_snafu::ProgressSnafuExt;
 use snafu::{IntoError, ResultExt, Snafu};
 
+use super::quickref::parse_quickref;
 use crate::encint::{parse_encint_be, ParseEncIntError};
 use crate::{Driver, Pos, Progress};
 
+/// A PMGI chunk of the directory index.
+///
+/// Each entry holds the first name of a chunk on the next lower level of the index tree, which is
+/// either another PMGI chunk or a PMGL chunk on the lowest level.
+#[derive(Debug)]
+pub struct IndexChunk<'a> {
+    pub(crate) entries: Vec<IndexChunkEntry<'a>>,
+}
+
 pub fn parse_index_chunk<'a>(
     chunk_size: usize,
-) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, (), ParseIndexChunkError> {
+    quickref_density: u32,
+) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, IndexChunk<'a>, ParseIndexChunkError> {
     move |pd, pos| {
         let (end_of_chunk, chunk_data) =
             try_parse!(pos.take(chunk_size).snafu_leaf(|pos| ChunkOutOfBounds {
@@ -39,25 +50,25 @@ pub fn parse_index_chunk<'a>(
             Err(e) => return Progress::failure(pos, e),
         };
 
-        let (_quickref_pos, rest_of_non_quickref_area) =
+        let (quickref_pos, rest_of_non_quickref_area) =
             pos.take(len_of_rest_of_non_quickref_area).unwrap();
 
-        // TODO: actually parse/validate quickref area
-        // let (_, _) = try_parse!(quickref_pos.take(quickref_len as usize));
-
         let pos = Pos {
             s: rest_of_non_quickref_area,
             ..pos
         };
 
-        // parse the entries, but don't save them
-        // (since we already save all directory chunk entries anyways)
-        let (_, _) = try_parse!(
-            zero_or_more_push_into(|| (), IndexChunkEntry::parse)(pd, pos)
-                .snafu(|pos| InvalidChunkEntry { offset: pos.offset })
-        );
+        let entries_start = pos.offset;
+        let (_, entries) = try_parse!(zero_or_more(|pd, pos: Pos<'a>| {
+            IndexChunkEntry::parse(pd, pos).map(|entry| (pos.offset - entries_start, entry))
+        })(pd, pos)
+        .snafu(|pos| InvalidChunkEntry { offset: pos.offset }));
+        let (entry_offsets, entries): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+
+        // the entries are searched directly, so the quickref area is only validated
+        parse_quickref(pd, quickref_pos, quickref_density, &entry_offsets);
 
-        Progress::success(end_of_chunk, ())
+        Progress::success(end_of_chunk, IndexChunk { entries })
     }
 }
 
@@ -72,7 +83,7 @@ fn tag<'a>(
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseIndexChunkError {
     #[snafu(display("Not enough data in the chunk"))]
     NotEnoughDataInChunk,
@@ -114,8 +125,8 @@ impl Recoverable for ParseIndexChunkError {
 
 #[derive(Debug)]
 pub struct IndexChunkEntry<'a> {
-    name: &'a str,
-    listing_chunk_starting_with_name: u64,
+    pub(crate) name: &'a str,
+    pub(crate) chunk_starting_with_name: u64,
 }
 
 impl<'a> IndexChunkEntry<'a> {
@@ -142,18 +153,18 @@ impl<'a> IndexChunkEntry<'a> {
                         .snafu_leaf(|_| NameStringOutOfBounds)
                         .and_then(pos, |s| std::str::from_utf8(s).context(NameInvalidUtf8))
                 };
-                let listing_chunk_starting_with_name =
+                let chunk_starting_with_name =
                     |pd, pos| parse_encint_be(pd, pos).snafu(|_| ChunkNumberInvalid);
             },
             Self {
                 name,
-                listing_chunk_starting_with_name,
+                chunk_starting_with_name,
             }
         )
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseIndexChunkEntryError {
     NotEnoughData,
     LengthOfNameInvalid { source: ParseEncIntError },
@@ -1,26 +1,34 @@
+use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::str::Utf8Error;
 
-use pahs::combinators::zero_or_more;
 use pahs::slice::num::u32_le;
 use pahs::slice::{tag, NotEnoughDataError};
 use pahs::{sequence, try_parse, Recoverable};
 use pahs_snafu::ProgressSnafuExt;
 use snafu::{IntoError, ResultExt, Snafu};
 
+use super::cmp_names;
+use super::quickref::parse_quickref;
 use crate::encint::{parse_encint_be, ParseEncIntError};
+use crate::recovery::SkippedListingEntries;
+use crate::validation::{reserved_u32, UnexpectedReservedValueError};
 use crate::{Driver, Pos, Progress};
 
 #[derive(Debug)]
 pub struct ListingChunk<'a> {
-    chunk_index_before: Option<u32>,
-    chunk_index_after: Option<u32>,
+    pub(crate) chunk_index_before: Option<u32>,
+    pub(crate) chunk_index_after: Option<u32>,
     pub(crate) entries: Vec<ListingChunkEntry<'a>>,
+    /// The indices of the entries referenced by the quickref area, empty if the area is
+    /// invalid or the entries aren't sorted
+    quickref: Vec<usize>,
 }
 
 impl<'a> ListingChunk<'a> {
     pub fn parse(
         chunk_size: usize,
+        quickref_density: u32,
     ) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, ListingChunk<'a>, ParseListingChunkError>
     {
         move |pd, pos| {
@@ -48,20 +56,18 @@ impl<'a> ListingChunk<'a> {
                 Err(e) => return Progress::failure(pos, e),
             };
 
-            let (_quickref_pos, rest_of_non_quickref_area) =
+            let (quickref_pos, rest_of_non_quickref_area) =
                 pos.take(len_of_rest_of_non_quickref_area).unwrap();
 
-            // TODO: actually parse/validate quickref area
-            // let (_, _) = try_parse!(quickref_pos.take(quickref_len as usize));
-
             let pos = Pos {
                 s: rest_of_non_quickref_area,
                 ..pos
             };
 
             // always 0 according to russotto's chm format spec, 7-zip.chm has 0D 00 00 00 here
-            // the value is unused, so just skip over these 4 bytes
-            let (pos, _) = try_parse!(pos.take(4));
+            let (pos, _) = try_parse!(reserved_u32::<ParseListingChunkError>("reserved", 0)(
+                pd, pos
+            ));
 
             let num_except_minus_one = |pd: &mut _, pos| {
                 u32_le(pd, pos).map(|i| if i == 0xFFFF_FFFF { None } else { Some(i) })
@@ -70,8 +76,48 @@ impl<'a> ListingChunk<'a> {
             let (pos, chunk_index_before) = try_parse!(num_except_minus_one(pd, pos));
             let (pos, chunk_index_after) = try_parse!(num_except_minus_one(pd, pos));
 
-            let (_, entries) = try_parse!(zero_or_more(ListingChunkEntry::parse)(pd, pos)
-                .snafu(|pos| InvalidChunkEntry { offset: pos.offset }));
+            let entries_start = pos.offset;
+            let mut entries = Vec::new();
+            let mut entry_offsets = Vec::new();
+            let mut pos = pos;
+
+            loop {
+                match ListingChunkEntry::parse(pd, pos).finish() {
+                    (next_pos, Ok(entry)) => {
+                        entry_offsets.push(pos.offset - entries_start);
+                        entries.push(entry);
+                        pos = next_pos;
+                    }
+                    // no more entries
+                    (_, Err(e)) if e.recoverable() => break,
+                    (failed_pos, Err(e)) => {
+                        let e = InvalidChunkEntry {
+                            offset: failed_pos.offset,
+                        }
+                        .into_error(e);
+
+                        if !pd.state.recover {
+                            return Progress::failure(failed_pos, e);
+                        }
+
+                        // the following entries can't be found without knowing where this one ends
+                        pd.state
+                            .recovered_errors
+                            .push(SkippedListingEntries { offset: pos.offset }.into_error(e));
+                        break;
+                    }
+                }
+            }
+
+            let mut quickref = parse_quickref(pd, quickref_pos, quickref_density, &entry_offsets);
+
+            // lookups through the quickref area rely on the order of the entries
+            let sorted = entries
+                .windows(2)
+                .all(|pair| cmp_names(pair[0].name, pair[1].name) != Ordering::Greater);
+            if !sorted {
+                quickref.clear();
+            }
 
             Progress::success(
                 end_of_chunk,
@@ -79,11 +125,72 @@ impl<'a> ListingChunk<'a> {
                     chunk_index_before,
                     chunk_index_after,
                     entries,
+                    quickref,
                 },
             )
         }
     }
 
+    /// Looks up the entry with exactly the given name.
+    pub fn find(&self, name: &str) -> Option<&ListingChunkEntry<'a>> {
+        self.candidates(name)
+            .iter()
+            .find(|entry| entry.name == name)
+    }
+
+    /// Looks up an entry with the given name, compared ASCII case-insensitively.
+    ///
+    /// An exact match is preferred over other matches.
+    pub fn find_ignore_ascii_case(&self, name: &str) -> Option<&ListingChunkEntry<'a>> {
+        let candidates = self.candidates(name);
+        candidates
+            .iter()
+            .find(|entry| entry.name == name)
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find(|entry| entry.name.eq_ignore_ascii_case(name))
+            })
+    }
+
+    /// Returns the entries that can have the given name.
+    ///
+    /// The entries are only searched through the quickref area if they are sorted. Otherwise,
+    /// or if the area is invalid, all entries are returned.
+    fn candidates(&self, name: &str) -> &[ListingChunkEntry<'a>] {
+        if self.quickref.is_empty() {
+            &self.entries
+        } else {
+            self.entries_matching(name)
+        }
+    }
+
+    /// Returns the entries whose names are ASCII case-insensitively equal to the given name,
+    /// assuming the entries are sorted.
+    ///
+    /// The quickref area is used to skip to the last referenced entry before the name.
+    fn entries_matching(&self, name: &str) -> &[ListingChunkEntry<'a>] {
+        let before = self
+            .quickref
+            .binary_search_by(|&i| cmp_names(self.entries[i].name, name).then(Ordering::Greater))
+            .unwrap_or_else(|i| i);
+        let start = before.checked_sub(1).map_or(0, |i| self.quickref[i]);
+
+        let entries = &self.entries[start..];
+        let first = entries
+            .iter()
+            .position(|entry| cmp_names(entry.name, name) != Ordering::Less)
+            .unwrap_or(entries.len());
+
+        let entries = &entries[first..];
+        let len = entries
+            .iter()
+            .position(|entry| cmp_names(entry.name, name) != Ordering::Equal)
+            .unwrap_or(entries.len());
+
+        &entries[..len]
+    }
+
     fn tag(
         expected: &'static [u8],
     ) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, &'a [u8], ParseListingChunkError> {
@@ -96,7 +203,7 @@ impl<'a> ListingChunk<'a> {
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseListingChunkError {
     #[snafu(display("Not enough data in the chunk"))]
     NotEnoughDataInChunk,
@@ -119,6 +226,11 @@ pub enum ParseListingChunkError {
         offset: usize,
         source: ParseListingChunkEntryError,
     },
+
+    #[snafu(display("Failed the pedantic validation:\n{}", source))]
+    UnexpectedReservedValue {
+        source: UnexpectedReservedValueError,
+    },
 }
 
 impl From<NotEnoughDataError> for ParseListingChunkError {
@@ -127,10 +239,17 @@ impl From<NotEnoughDataError> for ParseListingChunkError {
     }
 }
 
+impl From<UnexpectedReservedValueError> for ParseListingChunkError {
+    fn from(e: UnexpectedReservedValueError) -> Self {
+        UnexpectedReservedValue.into_error(e)
+    }
+}
+
 impl Recoverable for ParseListingChunkError {
     fn recoverable(&self) -> bool {
         match self {
             Self::InvalidChunkEntry { source, .. } => source.recoverable(),
+            Self::UnexpectedReservedValue { .. } => false,
             _ => true,
         }
     }
@@ -185,7 +304,7 @@ impl<'a> ListingChunkEntry<'a> {
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseListingChunkEntryError {
     NotEnoughData,
     LengthOfNameInvalid { source: ParseEncIntError },
@@ -202,3 +321,46 @@ impl Recoverable for ParseListingChunkEntryError {
         matches!(self, Self::NotEnoughData)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_file::{TestDirectory, DIRECTORY_HEADER_LEN, QUICKREF_DENSITY};
+
+    const CHUNK_SIZE: usize = 0x100;
+
+    fn chunk_data(names: &[&str]) -> Vec<u8> {
+        let mut directory = TestDirectory::new();
+        directory.chunk_size = CHUNK_SIZE;
+        let data = directory.add_listing_chunk(names).build();
+
+        data[DIRECTORY_HEADER_LEN..].to_vec()
+    }
+
+    fn parse(data: &[u8]) -> ListingChunk<'_> {
+        let pd = &mut Driver::with_state(Default::default());
+        ListingChunk::parse(CHUNK_SIZE, QUICKREF_DENSITY)(pd, Pos::new(data))
+            .finish()
+            .1
+            .unwrap()
+    }
+
+    #[test]
+    fn it_looks_up_entries_through_the_quickref_area_if_they_are_sorted() {
+        let data = chunk_data(&["/a", "/b", "/c", "/d", "/e", "/f", "/G"]);
+        let chunk = parse(&data);
+        assert_eq!(chunk.quickref, [5]);
+        assert_eq!(chunk.find("/b").unwrap().name, "/b");
+        assert_eq!(chunk.find_ignore_ascii_case("/g").unwrap().name, "/G");
+        assert!(chunk.find("/g").is_none());
+        assert!(chunk.find("/missing").is_none());
+
+        // `/g` comes before `/z`, so it can only be found by going through all entries
+        let data = chunk_data(&["/a", "/b", "/c", "/d", "/e", "/z", "/g"]);
+        let chunk = parse(&data);
+        assert!(chunk.quickref.is_empty());
+        assert_eq!(chunk.find("/g").unwrap().name, "/g");
+        assert_eq!(chunk.find_ignore_ascii_case("/Z").unwrap().name, "/z");
+        assert!(chunk.find("/missing").is_none());
+    }
+}
@@ -0,0 +1,139 @@
+use std::convert::TryInto;
+
+use crate::warning::{QuickrefEntryCountMismatch, QuickrefOffsetMismatch, QuickrefTooShort};
+use crate::{Driver, Pos};
+
+/// Decodes and validates the quickref area at the end of a directory chunk.
+///
+/// The last word of the area is the number of entries in the chunk. It is preceded by the
+/// offsets of every `1 + (1 << quickref_density)`th entry, relative to the first entry and
+/// stored backwards from the end of the chunk.
+///
+/// `entry_offsets` are the offsets of the parsed entries, relative to the first entry. Returns
+/// the indices of the entries referenced by the quickref area, or an empty list if the area is
+/// invalid, in which case a warning is reported.
+pub fn parse_quickref(
+    pd: &mut Driver,
+    quickref_area: Pos<'_>,
+    quickref_density: u32,
+    entry_offsets: &[usize],
+) -> Vec<usize> {
+    let area = quickref_area.s;
+    let word = |i: usize| -> Option<usize> {
+        let end = area.len().checked_sub(2 * i)?;
+        let bytes = area.get(end.checked_sub(2)?..end)?;
+        Some(u16::from_le_bytes(bytes.try_into().unwrap()).into())
+    };
+
+    let offset = quickref_area.offset;
+    let warnings = &mut pd.state.warnings;
+
+    let entry_count = match word(0) {
+        Some(count) => count,
+        None => {
+            warnings.push(QuickrefTooShort { offset }.build());
+            return Vec::new();
+        }
+    };
+
+    if entry_count != entry_offsets.len() {
+        warnings.push(
+            QuickrefEntryCountMismatch {
+                offset,
+                count: entry_count,
+                entries: entry_offsets.len(),
+            }
+            .build(),
+        );
+    }
+
+    let interval = match 1usize
+        .checked_shl(quickref_density)
+        .and_then(|step| step.checked_add(1))
+    {
+        Some(interval) => interval,
+        None => return Vec::new(),
+    };
+
+    let mut quickref = Vec::new();
+
+    for (i, entry) in (interval..entry_offsets.len())
+        .step_by(interval)
+        .enumerate()
+    {
+        let entry_offset = match word(i + 1) {
+            Some(entry_offset) => entry_offset,
+            None => {
+                warnings.push(QuickrefTooShort { offset }.build());
+                return Vec::new();
+            }
+        };
+
+        if entry_offset != entry_offsets[entry] {
+            warnings.push(QuickrefOffsetMismatch { offset, entry }.build());
+            return Vec::new();
+        }
+
+        quickref.push(entry);
+    }
+
+    quickref
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ParseWarning;
+
+    fn parse(area: &[u8], entry_offsets: &[usize]) -> (Vec<usize>, Vec<ParseWarning>) {
+        let pd = &mut Driver::with_state(Default::default());
+        let pos = Pos {
+            offset: 0x100,
+            s: area,
+        };
+
+        let quickref = parse_quickref(pd, pos, 1, entry_offsets);
+        (quickref, pd.state.warnings.clone())
+    }
+
+    #[test]
+    fn it_validates_quickref_offsets() {
+        // density 1: every third entry is referenced
+        let entry_offsets = &[0, 0x10, 0x20, 0x30, 0x40, 0x50, 0x60];
+
+        let area = &[0, 0, 0x60, 0, 0x30, 0, 7, 0];
+        assert_eq!(parse(area, entry_offsets), (vec![3, 6], vec![]));
+
+        let area = &[0x60, 0, 0x31, 0, 7, 0];
+        assert_eq!(
+            parse(area, entry_offsets),
+            (
+                vec![],
+                vec![ParseWarning::QuickrefOffsetMismatch {
+                    offset: 0x100,
+                    entry: 3
+                }]
+            )
+        );
+
+        let area = &[0x30, 0, 7, 0];
+        assert_eq!(
+            parse(area, entry_offsets),
+            (
+                vec![],
+                vec![ParseWarning::QuickrefTooShort { offset: 0x100 }]
+            )
+        );
+
+        let area = &[0x30, 0, 4, 0];
+        assert_eq!(parse(area, &entry_offsets[..4]), (vec![3], vec![]));
+        assert_eq!(
+            parse(area, &entry_offsets[..5]).1,
+            [ParseWarning::QuickrefEntryCountMismatch {
+                offset: 0x100,
+                count: 4,
+                entries: 5
+            }]
+        );
+    }
+}
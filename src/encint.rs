@@ -48,7 +48,7 @@ pub fn parse_encint_be<'a>(
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 pub enum ParseEncIntError {
     #[snafu(display("Not enough data in the input"))]
     NotEnoughData,
@@ -0,0 +1,226 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use snafu::{ResultExt, Snafu};
+
+use crate::directory_listing::ParseDirectoryListingError;
+use crate::lzx::{DecompressError, MsCompressedSection};
+use crate::{ChmFile, Entry, ReadError};
+
+/// The number of reset blocks decompressed by one task.
+///
+/// Small enough to spread the work of a typical file over all cores, large enough that the
+/// tasks aren't dominated by opening the output files.
+const BLOCKS_PER_TASK: u64 = 16;
+
+/// Extracts all entries outside of the `::` namespace into `dir`, see
+/// [`ChmFile::extract_all`].
+pub(crate) fn extract_all(
+    chm_file: &ChmFile<'_>,
+    section: Option<&MsCompressedSection<'_>>,
+    dir: &Path,
+) -> Result<(), ExtractError> {
+    let mut compressed_entries = Vec::new();
+
+    for entry in chm_file.entries() {
+        let entry = entry.context(ReadDirectory)?;
+        let path = match output_path(dir, entry.name)? {
+            Some(path) => path,
+            None => continue,
+        };
+
+        if entry.name.ends_with('/') {
+            fs::create_dir_all(&path).context(CreateDirectory { path })?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(CreateDirectory { path: parent })?;
+        }
+
+        match (entry.content_section, section) {
+            (1, Some(section)) => {
+                if !matches!(entry.offset.checked_add(entry.length), Some(end) if end <= section.len())
+                {
+                    return EntryOutOfBounds { name: entry.name }.fail();
+                }
+
+                // created up front, the data is written by the tasks below
+                File::create(&path)
+                    .and_then(|file| file.set_len(entry.length))
+                    .context(WriteFile { path: &path })?;
+                compressed_entries.push((entry, path));
+            }
+            _ => {
+                let data = chm_file.read_file(entry.name).context(ReadEntry)?;
+                fs::write(&path, data).context(WriteFile { path })?;
+            }
+        }
+    }
+
+    let section = match section {
+        Some(section) => section,
+        None => return Ok(()),
+    };
+
+    let end = compressed_entries
+        .iter()
+        .map(|(entry, _)| entry.offset + entry.length)
+        .max()
+        .unwrap_or(0);
+    let block_count = match end {
+        0 => 0,
+        end => (end - 1) / section.reset_block_size() + 1,
+    };
+
+    // the LZX state is reset at the start of every block, so they can be decompressed
+    // independently of each other
+    let tasks: Vec<Range<u64>> = (0..block_count)
+        .step_by(BLOCKS_PER_TASK as usize)
+        .map(|start| start..(start + BLOCKS_PER_TASK).min(block_count))
+        .collect();
+    let extract_task =
+        |blocks: &Range<u64>| extract_blocks(section, &compressed_entries, blocks.clone());
+
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        tasks.par_iter().try_for_each(extract_task)
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        tasks.iter().try_for_each(extract_task)
+    }
+}
+
+/// Decompresses the given reset blocks and writes the parts of the entries stored in them to
+/// the already created output files.
+fn extract_blocks(
+    section: &MsCompressedSection<'_>,
+    entries: &[(Entry<'_>, PathBuf)],
+    blocks: Range<u64>,
+) -> Result<(), ExtractError> {
+    let block_size = section.reset_block_size();
+    let start = blocks.start * block_size;
+    let end = blocks.end * block_size;
+
+    let entries: Vec<_> = entries
+        .iter()
+        .filter(|(entry, _)| entry.offset < end && entry.offset + entry.length > start)
+        .collect();
+
+    for block in blocks {
+        let data = section
+            .decompress_reset_block(block)
+            .context(Decompress { block })?;
+        let block_start = block * block_size;
+        let block_end = block_start + data.len() as u64;
+
+        for (entry, path) in &entries {
+            let from = entry.offset.max(block_start);
+            let to = (entry.offset + entry.length).min(block_end);
+            if from >= to {
+                continue;
+            }
+
+            // both ends are inside of the block, so they fit into a usize
+            let data = &data[(from - block_start) as usize..(to - block_start) as usize];
+
+            OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|mut file| {
+                    file.seek(SeekFrom::Start(from - entry.offset))?;
+                    file.write_all(data)
+                })
+                .context(WriteFile { path })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The path an entry is extracted to, or `None` for entries of the internal `::` namespace.
+fn output_path(dir: &Path, name: &str) -> Result<Option<PathBuf>, ExtractError> {
+    let relative = match name.strip_prefix('/') {
+        Some(relative) => relative,
+        None => return Ok(None),
+    };
+
+    let mut path = dir.to_path_buf();
+    for component in relative
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        // don't let entries escape the output directory
+        if component == "." || component == ".." || component.contains(&['\\', ':'][..]) {
+            return UnsafePath { name }.fail();
+        }
+        path.push(component);
+    }
+
+    Ok(Some(path))
+}
+
+#[derive(Debug, Snafu)]
+pub enum ExtractError {
+    #[snafu(display(
+        "The entry `{}` has a path that would leave the output directory",
+        name
+    ))]
+    UnsafePath { name: String },
+
+    #[snafu(display("The entry `{}` is out of the bounds of its content section", name))]
+    EntryOutOfBounds { name: String },
+
+    #[snafu(display("Failed to create the directory `{}`:\n{}", path.display(), source))]
+    CreateDirectory { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to write the file `{}`:\n{}", path.display(), source))]
+    WriteFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to read the directory:\n{}", source))]
+    ReadDirectory { source: ParseDirectoryListingError },
+
+    #[snafu(display("Failed to read an entry:\n{}", source))]
+    ReadEntry { source: ReadError },
+
+    #[snafu(display("Failed to decompress the reset block {}:\n{}", block, source))]
+    Decompress { block: u64, source: DecompressError },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_maps_entry_names_to_paths() {
+        let dir = Path::new("out");
+
+        assert_eq!(
+            output_path(dir, "/html/topic.htm").unwrap(),
+            Some(dir.join("html").join("topic.htm"))
+        );
+        assert_eq!(output_path(dir, "/html/").unwrap(), Some(dir.join("html")));
+        assert_eq!(
+            output_path(dir, "/#SYSTEM").unwrap(),
+            Some(dir.join("#SYSTEM"))
+        );
+        assert_eq!(output_path(dir, "::DataSpace/NameList").unwrap(), None);
+
+        for name in &[
+            "/../evil.htm",
+            "/a/./b.htm",
+            "/a\\..\\b.htm",
+            "/C:/evil.htm",
+        ] {
+            assert!(matches!(
+                output_path(dir, name),
+                Err(ExtractError::UnsafePath { .. })
+            ));
+        }
+    }
+}
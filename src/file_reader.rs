@@ -0,0 +1,254 @@
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use crate::lzx::MsCompressedSection;
+use crate::source::Region;
+
+/// A reader over a single file inside of a CHM file.
+///
+/// Files in the MSCompressed section are decompressed lazily, one LZX reset block at a time,
+/// as the cursor moves through the file.
+#[derive(Debug)]
+pub struct FileReader<'a> {
+    source: Source<'a>,
+    len: u64,
+    pos: u64,
+}
+
+#[derive(Debug)]
+enum Source<'a> {
+    Uncompressed(Region<'a>),
+    Compressed {
+        section: &'a MsCompressedSection<'a>,
+        offset: u64,
+        /// The index and data of the most recently used reset block
+        block: Option<(u64, Arc<[u8]>)>,
+    },
+}
+
+impl<'a> FileReader<'a> {
+    pub(crate) fn uncompressed(data: Region<'a>) -> Self {
+        Self {
+            len: data.len(),
+            source: Source::Uncompressed(data),
+            pos: 0,
+        }
+    }
+
+    pub(crate) fn compressed(section: &'a MsCompressedSection<'a>, offset: u64, len: u64) -> Self {
+        Self {
+            source: Source::Compressed {
+                section,
+                offset,
+                block: None,
+            },
+            len,
+            pos: 0,
+        }
+    }
+
+    /// The length of the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let remaining = self.len - self.pos;
+
+        let n = match &mut self.source {
+            Source::Uncompressed(region) => {
+                let n = (buf.len() as u64).min(remaining) as usize;
+                // `pos + n <= len`, so the range is inside of the region
+                let data = region.slice(self.pos, n as u64).unwrap().read()?;
+                buf[..n].copy_from_slice(&data);
+                n
+            }
+            Source::Compressed {
+                section,
+                offset,
+                block,
+            } => {
+                let block_size = section.reset_block_size();
+                let section_offset = *offset + self.pos;
+                let block_index = section_offset / block_size;
+
+                let data = match block {
+                    Some((index, data)) if *index == block_index => data,
+                    _ => {
+                        let data = section
+                            .reset_block(block_index)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                        *block = Some((block_index, data));
+                        &block.as_ref().unwrap().1
+                    }
+                };
+
+                let data = &data[(section_offset - block_index * block_size) as usize..];
+                let n = (buf.len() as u64).min(data.len() as u64).min(remaining) as usize;
+                buf[..n].copy_from_slice(&data[..n]);
+                n
+            }
+        };
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FileReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => offset_by(self.len, delta),
+            SeekFrom::Current(delta) => offset_by(self.pos, delta),
+        };
+
+        match new_pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn offset_by(base: u64, delta: i64) -> Option<u64> {
+    if delta >= 0 {
+        base.checked_add(u64::try_from(delta).ok()?)
+    } else {
+        base.checked_sub(delta.wrapping_neg() as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_file::TestFile;
+    use crate::{ChmFile, ReadError};
+
+    #[test]
+    fn it_reads_and_seeks() {
+        let mut reader = FileReader::uncompressed(Region::from(&b"0123456789"[..]));
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"0123");
+
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 7);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"789");
+
+        assert_eq!(reader.seek(SeekFrom::Current(-5)).unwrap(), 5);
+        assert!(reader.seek(SeekFrom::Current(-6)).is_err());
+
+        assert_eq!(reader.seek(SeekFrom::Start(20)).unwrap(), 20);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    /// Builds a file with a compressed entry spanning three LZX reset blocks of 0x10000 bytes,
+    /// starting 7 bytes into the MSCompressed section.
+    fn file_with_large_entry() -> (Vec<u8>, Vec<u8>) {
+        let content: Vec<u8> = (0..0x24000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut file = TestFile::new();
+        file.add_file("/index.htm", b"<p>index</p>")
+            .add_compressed_file("/a.htm", b"<p></p>")
+            .add_compressed_file("/video.bin", &content);
+
+        (file.build(), content)
+    }
+
+    #[test]
+    fn it_reads_compressed_files_across_reset_blocks() {
+        let (data, content) = file_with_large_entry();
+        let chm_file = ChmFile::load(&data).unwrap();
+
+        let mut reader = chm_file.open("/video.bin").unwrap();
+        assert_eq!(reader.len(), content.len() as u64);
+
+        // the first reset block ends at 0xFFF9 in the file
+        let mut buf = [0; 0x20];
+        assert_eq!(reader.seek(SeekFrom::Start(0xFFF0)).unwrap(), 0xFFF0);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &content[0xFFF0..0x10010]);
+
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert!(all == content);
+
+        let mut reader = chm_file.open("/index.htm").unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, b"<p>index</p>");
+
+        assert!(matches!(
+            chm_file.open("/missing.htm"),
+            Err(ReadError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn it_seeks_back_into_earlier_reset_blocks() {
+        let (data, content) = file_with_large_entry();
+        let chm_file = ChmFile::load(&data).unwrap();
+        chm_file.set_block_cache_capacity(0);
+
+        let mut reader = chm_file.open("/video.bin").unwrap();
+        let mut buf = [0; 0x10];
+
+        assert_eq!(reader.seek(SeekFrom::End(-0x10)).unwrap(), 0x23FF0);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &content[0x23FF0..]);
+
+        // back into the first block
+        assert_eq!(reader.seek(SeekFrom::Current(-0x23000)).unwrap(), 0x1000);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &content[0x1000..0x1010]);
+
+        // forward into the block in between
+        assert_eq!(reader.seek(SeekFrom::Start(0x18000)).unwrap(), 0x18000);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &content[0x18000..0x18010]);
+    }
+
+    #[test]
+    fn it_stops_reading_at_the_end_of_compressed_files() {
+        let (data, content) = file_with_large_entry();
+        let chm_file = ChmFile::load(&data).unwrap();
+
+        let mut reader = chm_file.open("/video.bin").unwrap();
+        let mut buf = [0; 0x10];
+
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], &content[content.len() - 4..]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        assert_eq!(reader.seek(SeekFrom::End(0x10)).unwrap(), 0x24010);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        // the entry following `/a.htm` in the section isn't part of it
+        let mut reader = chm_file.open("/a.htm").unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, b"<p></p>");
+    }
+}
@@ -3,11 +3,12 @@ use pahs::slice::num::{u32_le, u64_le};
 use pahs::slice::{tag, NotEnoughDataError};
 use pahs::{sequence, Recoverable};
 use pahs_snafu::ProgressSnafuExt;
-use snafu::Snafu;
+use snafu::{IntoError, Snafu};
 use uuid::Uuid;
 
 use super::uuid::parse_exact_uuid;
 use super::{Driver, Pos, Progress};
+use crate::validation::{reserved_u32, UnexpectedReservedValueError};
 
 const HEADER_GUID_1: Uuid = Uuid::from_bytes(hex!("10 FD017CAA7BD0119E0C00A0C922E6EC"));
 const HEADER_GUID_2: Uuid = Uuid::from_bytes(hex!("11 FD017CAA7BD0119E0C00A0C922E6EC"));
@@ -34,7 +35,7 @@ impl Header {
                 Self::tag(TAG);
                 let version = u32_le;
                 let total_header_length = u32_le;
-                let unknown_dword = u32_le;
+                let unknown_dword = reserved_u32::<ParseHeaderError>("unknown_dword", 1);
                 let timestamp = u32_le;
                 let language_id = u32_le;
 
@@ -97,6 +98,11 @@ pub enum ParseHeaderError {
     HeaderSectionTableFailed {
         source: ParseHeaderSectionTableError,
     },
+
+    #[snafu(display("Failed the pedantic validation:\n{}", source))]
+    UnexpectedReservedValue {
+        source: UnexpectedReservedValueError,
+    },
 }
 
 impl Recoverable for ParseHeaderError {
@@ -106,6 +112,7 @@ impl Recoverable for ParseHeaderError {
             Self::InvalidTag { .. } => true,
             Self::ParseUuidFailed { source } => source.recoverable(),
             Self::HeaderSectionTableFailed { source } => source.recoverable(),
+            Self::UnexpectedReservedValue { .. } => false,
         }
     }
 }
@@ -116,6 +123,12 @@ impl From<NotEnoughDataError> for ParseHeaderError {
     }
 }
 
+impl From<UnexpectedReservedValueError> for ParseHeaderError {
+    fn from(e: UnexpectedReservedValueError) -> Self {
+        UnexpectedReservedValue.into_error(e)
+    }
+}
+
 #[derive(Debug)]
 pub struct HeaderSectionTableEntry {
     pub file_offset: u64,
@@ -2,9 +2,11 @@ use pahs::slice::tag;
 use pahs::slice::{num::*, NotEnoughDataError};
 use pahs::{sequence, Recoverable};
 use pahs_snafu::ProgressSnafuExt;
-use snafu::Snafu;
+use snafu::{IntoError, Snafu};
 
 use super::{Driver, Pos, Progress};
+use crate::recovery::FileSizeMismatch as RecoveredFileSizeMismatch;
+use crate::validation::{reserved_u32, UnexpectedReservedValueError};
 
 #[derive(Debug)]
 pub struct HeaderSection0 {
@@ -27,13 +29,25 @@ impl HeaderSection0 {
                 {
                     Self::tag(TAG);
 
-                    let unknown_dword_1 = u32_le;
-                    let file_size = |pd, p| {
+                    let unknown_dword_1 =
+                        reserved_u32::<ParseHeaderSection0Error>("unknown_dword_1", 0);
+                    let file_size = |pd: &mut Driver, p: Pos<'a>| {
                         u64_le(pd, p)
                             .snafu_leaf(|_| NotEnoughData)
                             .and_then(p, |size| {
                                 if size == expected_file_size {
                                     Ok(size)
+                                } else if pd.state.recover {
+                                    // most likely a truncated file, the rest may still be usable
+                                    pd.state.recovered_errors.push(
+                                        RecoveredFileSizeMismatch {
+                                            offset: p.offset,
+                                            expected: expected_file_size,
+                                            parsed: size,
+                                        }
+                                        .build(),
+                                    );
+                                    Ok(size)
                                 } else {
                                     Err(FileSizeMismatch {
                                         expected: expected_file_size,
@@ -44,8 +58,10 @@ impl HeaderSection0 {
                             })
                     };
 
-                    let unknown_dword_2 = u32_le;
-                    let unknown_dword_3 = u32_le;
+                    let unknown_dword_2 =
+                        reserved_u32::<ParseHeaderSection0Error>("unknown_dword_2", 0);
+                    let unknown_dword_3 =
+                        reserved_u32::<ParseHeaderSection0Error>("unknown_dword_3", 0);
                 },
                 Self {
                     unknown_dword_1,
@@ -82,6 +98,11 @@ pub enum ParseHeaderSection0Error {
 
     #[snafu(display("The file size in the section doesn't match the file size. File size: {:#X}, Parsed: {:#X},", expected, parsed))]
     FileSizeMismatch { expected: u64, parsed: u64 },
+
+    #[snafu(display("Failed the pedantic validation:\n{}", source))]
+    UnexpectedReservedValue {
+        source: UnexpectedReservedValueError,
+    },
 }
 
 impl From<NotEnoughDataError> for ParseHeaderSection0Error {
@@ -90,12 +111,19 @@ impl From<NotEnoughDataError> for ParseHeaderSection0Error {
     }
 }
 
+impl From<UnexpectedReservedValueError> for ParseHeaderSection0Error {
+    fn from(e: UnexpectedReservedValueError) -> Self {
+        UnexpectedReservedValue.into_error(e)
+    }
+}
+
 impl Recoverable for ParseHeaderSection0Error {
     fn recoverable(&self) -> bool {
         match self {
             Self::NotEnoughData => true,
             Self::InvalidTag { .. } => true,
             Self::FileSizeMismatch { .. } => false,
+            Self::UnexpectedReservedValue { .. } => false,
         }
     }
 }
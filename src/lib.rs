@@ -1,22 +1,66 @@
 #![forbid(rust_2018_idioms)]
 #![deny(nonstandard_style)]
 
+mod block_cache;
 mod chm_file;
 mod chm_file_head;
+mod control_data;
+mod extract;
+mod file_reader;
 mod name_list;
+mod owned;
+mod recovery;
+mod system;
+mod topics;
+mod tree;
+mod validation;
+mod warning;
 
-pub use chm_file::{ChmFile, ParseChmFileError};
+pub use block_cache::CacheStats;
+#[cfg(feature = "mmap")]
+pub use chm_file::OpenMmapError;
+pub use chm_file::{ChmFile, Entry, ParseChmFileError, ReadError};
 pub use chm_file_head::{ChmFileHead, ParseChmFileHeadError};
+pub use control_data::{ControlData, ParseControlDataError};
+pub use directory_listing::ParseDirectoryListingError;
+pub use extract::ExtractError;
+pub use file_reader::FileReader;
+pub use link::{ItsUrl, ParseItsUrlError};
+pub use lzx::DecompressError;
+pub use owned::OwnedChmFile;
+pub use recovery::RecoveredError;
+pub use system::{ParseSystemError, ReadSystemError, System, SystemRecord};
+pub use topics::{ParseTopicTableError, ReadTopicTableError, Topic, TopicTable};
+pub use tree::{Directory, DirectoryTree, Node, Walk};
+pub use validation::ValidationPolicy;
+pub use warning::ParseWarning;
 
 mod directory_listing;
 mod encint;
 mod header;
 mod header_section_0;
+mod link;
+mod lzx;
+mod reset_table;
+mod source;
+mod span_info;
+mod sync;
+#[cfg(test)]
+mod test_file;
 mod uuid;
 
 #[derive(Debug, Default)]
 pub struct ParseState {
-    pub warnings: Vec<(usize, &'static str)>,
+    pub warnings: Vec<ParseWarning>,
+    /// Only parse the directory header up front, and the directory chunks when they are first
+    /// accessed
+    pub lazy_directory: bool,
+    /// Skip over invalid parts of the file instead of failing, collecting the errors in
+    /// `recovered_errors`
+    pub recover: bool,
+    pub recovered_errors: Vec<RecoveredError>,
+    /// How strictly reserved and unknown fields are checked
+    pub validation: ValidationPolicy,
 }
 
 pub type Pos<'a> = pahs::slice::BytePos<'a>;
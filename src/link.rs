@@ -0,0 +1,299 @@
+use snafu::Snafu;
+
+/// URL schemes that refer to a file inside of a CHM file, in lower case.
+const ITS_SCHEMES: &[&str] = &["ms-its:", "mk:@msitstore:", "its:"];
+
+/// A parsed `ms-its:`, `mk:@MSITStore:` or `its:` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItsUrl {
+    /// The (percent-decoded) name or path of the CHM file
+    pub chm_file: String,
+    /// The absolute path inside of the CHM file, or `None` if the URL refers to the CHM file
+    /// itself
+    pub path: Option<String>,
+    pub fragment: Option<String>,
+}
+
+impl ItsUrl {
+    /// Parses an URL like `ms-its:other.chm::/topic.htm#anchor` or
+    /// `mk:@MSITStore:C:\help\file.chm::/path/topic.htm`.
+    pub fn parse(url: &str) -> Result<Self, ParseItsUrlError> {
+        let rest = ITS_SCHEMES
+            .iter()
+            .find_map(|scheme| {
+                url.get(..scheme.len())
+                    .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+                    .map(|_| &url[scheme.len()..])
+            })
+            .ok_or_else(|| UnknownScheme.build())?;
+
+        let (chm_file, path) = match rest.find("::") {
+            Some(i) => (&rest[..i], Some(&rest[i + 2..])),
+            None => (strip_fragment_and_query(rest), None),
+        };
+
+        let chm_file = percent_decode(chm_file.trim()).ok_or_else(|| InvalidEncoding.build())?;
+        if chm_file.is_empty() {
+            return MissingFileName.fail();
+        }
+
+        // the fragment follows the path, or the file name if there is no path
+        let with_fragment = path.unwrap_or(rest);
+        let fragment = with_fragment
+            .find('#')
+            .map(|i| &with_fragment[i + 1..])
+            .map(|fragment| percent_decode(fragment).ok_or_else(|| InvalidEncoding.build()))
+            .transpose()?;
+
+        let path = match path.map(strip_fragment_and_query) {
+            Some("") | None => None,
+            Some(path) if has_scheme(path) => return PathWithScheme.fail(),
+            Some(path) => Some(resolve_relative("/", path).ok_or_else(|| InvalidEncoding.build())?),
+        };
+
+        Ok(Self {
+            chm_file,
+            path,
+            fragment,
+        })
+    }
+
+    /// Returns `true` if the URL refers to the CHM file with the given name or path.
+    ///
+    /// Only the file names are compared, ASCII case-insensitively, as the paths in URLs are
+    /// usually relative to some unknown directory.
+    pub fn refers_to(&self, chm_file: &str) -> bool {
+        file_name(&self.chm_file).eq_ignore_ascii_case(file_name(chm_file))
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rfind(&['/', '\\'][..])
+        .map_or(path, |i| &path[i + 1..])
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseItsUrlError {
+    #[snafu(display("The URL doesn't start with `ms-its:`, `mk:@MSITStore:` or `its:`"))]
+    UnknownScheme,
+
+    #[snafu(display("The URL doesn't contain a CHM file name"))]
+    MissingFileName,
+
+    #[snafu(display("The path inside of the CHM file starts with another URL scheme"))]
+    PathWithScheme,
+
+    #[snafu(display("The URL isn't valid UTF-8 after percent-decoding"))]
+    InvalidEncoding,
+}
+
+/// Resolves a link found in the topic at `base` to an absolute path inside of the CHM file.
+///
+/// Fragments and query strings are removed, percent-encoded characters are decoded, backslashes
+/// are treated as path separators, and `.` and `..` segments are resolved.
+///
+/// Returns `None` for links with a URL scheme (e.g. `http:`), which can't point into the same
+/// CHM file without further processing, and for links that aren't valid UTF-8 after decoding.
+pub(crate) fn resolve_relative(base: &str, link: &str) -> Option<String> {
+    let link = strip_fragment_and_query(link);
+
+    if has_scheme(link) {
+        return None;
+    }
+
+    let link = percent_decode(link)?.replace('\\', "/");
+    let base = base.replace('\\', "/");
+
+    // a link to a fragment or with a different query string points to the base topic itself
+    if link.is_empty() {
+        return Some(normalize(&base));
+    }
+
+    let joined = if link.starts_with('/') {
+        link
+    } else {
+        // the link is relative to the directory containing the base topic
+        let base_dir = base.rfind('/').map_or("/", |i| &base[..=i]);
+        format!("{}{}", base_dir, link)
+    };
+
+    Some(normalize(&joined))
+}
+
+fn strip_fragment_and_query(link: &str) -> &str {
+    link.find(&['#', '?'][..]).map_or(link, |i| &link[..i])
+}
+
+/// Returns `true` if the link starts with a URL scheme like `http:` or `ms-its:`.
+fn has_scheme(link: &str) -> bool {
+    match link.find(':') {
+        Some(i) => {
+            let scheme = &link[..i];
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+/// Decodes `%XX` escapes. Invalid escapes are kept as they are.
+fn percent_decode(s: &str) -> Option<String> {
+    if !s.contains('%') {
+        return Some(s.to_owned());
+    }
+
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' {
+            bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Resolves `.` and `..` segments and removes empty segments of an absolute path.
+fn normalize(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let trailing_slash =
+        path.ends_with('/') || path.ends_with("/.") || path.ends_with("/..") || segments.is_empty();
+
+    let mut normalized = String::with_capacity(path.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing_slash {
+        normalized.push('/');
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_resolves_relative_links() {
+        let cases = &[
+            ("/html/topic.htm", "other.htm", "/html/other.htm"),
+            ("/html/topic.htm", "./other.htm#anchor", "/html/other.htm"),
+            ("/html/topic.htm", "../Images/Foo.GIF", "/Images/Foo.GIF"),
+            ("/html/topic.htm", "foo%20bar.htm?x=1", "/html/foo bar.htm"),
+            ("/html/topic.htm", "sub\\page.htm", "/html/sub/page.htm"),
+            ("/html/topic.htm", "/abs/page.htm", "/abs/page.htm"),
+            ("/html/topic.htm", "../../../up.htm", "/up.htm"),
+            ("/html/sub/topic.htm", "..", "/html/"),
+            ("/topic.htm", "100%.htm", "/100%.htm"),
+            ("/html/topic.htm", "#anchor", "/html/topic.htm"),
+            ("/html/topic.htm", "?x=1", "/html/topic.htm"),
+            ("html\\topic.htm", "", "/html/topic.htm"),
+        ];
+
+        for &(base, link, expected) in cases {
+            assert_eq!(
+                resolve_relative(base, link).as_deref(),
+                Some(expected),
+                "{} + {}",
+                base,
+                link
+            );
+        }
+    }
+
+    #[test]
+    fn it_parses_its_urls() {
+        assert_eq!(
+            ItsUrl::parse("ms-its:other.chm::/html/topic.htm#anchor").unwrap(),
+            ItsUrl {
+                chm_file: "other.chm".to_owned(),
+                path: Some("/html/topic.htm".to_owned()),
+                fragment: Some("anchor".to_owned()),
+            }
+        );
+        assert_eq!(
+            ItsUrl::parse("mk:@MSITStore:C:\\Help\\File.chm::topic%20a.htm").unwrap(),
+            ItsUrl {
+                chm_file: "C:\\Help\\File.chm".to_owned(),
+                path: Some("/topic a.htm".to_owned()),
+                fragment: None,
+            }
+        );
+        assert_eq!(
+            ItsUrl::parse("ITS:file.chm").unwrap(),
+            ItsUrl {
+                chm_file: "file.chm".to_owned(),
+                path: None,
+                fragment: None,
+            }
+        );
+        assert_eq!(
+            ItsUrl::parse("ms-its:file.chm?x=1#anchor%201").unwrap(),
+            ItsUrl {
+                chm_file: "file.chm".to_owned(),
+                path: None,
+                fragment: Some("anchor 1".to_owned()),
+            }
+        );
+
+        assert!(ItsUrl::parse("ms-its:C:\\Help\\file.chm::/a.htm")
+            .unwrap()
+            .refers_to("/home/user/FILE.CHM"));
+        assert!(matches!(
+            ItsUrl::parse("http://example.com/"),
+            Err(ParseItsUrlError::UnknownScheme)
+        ));
+        assert!(matches!(
+            ItsUrl::parse("ms-its:::/a.htm"),
+            Err(ParseItsUrlError::MissingFileName)
+        ));
+        assert!(matches!(
+            ItsUrl::parse("ms-its:file.chm::http://example.com/"),
+            Err(ParseItsUrlError::PathWithScheme)
+        ));
+        assert!(matches!(
+            ItsUrl::parse("ms-its:file.chm::/%FF.htm"),
+            Err(ParseItsUrlError::InvalidEncoding)
+        ));
+    }
+
+    #[test]
+    fn it_ignores_links_with_a_scheme() {
+        assert_eq!(resolve_relative("/a.htm", "http://example.com/"), None);
+        assert_eq!(resolve_relative("/a.htm", "ms-its:other.chm::/a.htm"), None);
+    }
+}
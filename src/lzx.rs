@@ -0,0 +1,270 @@
+use std::convert::TryFrom;
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+
+use lzxd::{Lzxd, WindowSize};
+use snafu::{ResultExt, Snafu};
+
+use crate::block_cache::BlockCache;
+use crate::control_data::ControlData;
+use crate::reset_table::ResetTable;
+use crate::source::Region;
+
+/// The MSCompressed content section (content section 1).
+///
+/// Data is decompressed on demand, one reset block (the data between two LZX reset points) at a
+/// time. Recently used blocks are kept in an LRU cache.
+#[derive(Debug)]
+pub(crate) struct MsCompressedSection<'a> {
+    compressed: Region<'a>,
+    reset_table: ResetTable,
+    window_size: WindowSize,
+    frame_size: usize,
+    frames_per_reset: usize,
+    cache: BlockCache,
+}
+
+impl<'a> MsCompressedSection<'a> {
+    pub fn new(
+        compressed: Region<'a>,
+        control_data: &ControlData,
+        reset_table: ResetTable,
+    ) -> Result<Self, DecompressError> {
+        let window_size = lzxd_window_size(control_data.window_size)?;
+
+        let frame_size =
+            usize::try_from(reset_table.block_size).map_err(|_| UnsupportedFrameSize.build())?;
+
+        let reset_interval = u64::from(control_data.reset_interval);
+        if reset_interval == 0 || reset_interval % reset_table.block_size != 0 {
+            return ResetIntervalNotFrameAligned {
+                reset_interval: control_data.reset_interval,
+                block_size: reset_table.block_size,
+            }
+            .fail();
+        }
+        let frames_per_reset = (reset_interval / reset_table.block_size) as usize;
+
+        let frame_count = reset_table.block_addresses.len() as u64;
+        if frame_count.saturating_mul(reset_table.block_size) < reset_table.uncompressed_length {
+            return TooFewFrames {
+                frames: reset_table.block_addresses.len(),
+                uncompressed_length: reset_table.uncompressed_length,
+            }
+            .fail();
+        }
+
+        // keep at least one block, so that sequential small reads don't decompress it again
+        let cache_blocks = control_data.cache_size_in_bytes() / reset_interval;
+        let cache = BlockCache::new(cache_blocks.max(1) as usize);
+
+        Ok(Self {
+            compressed,
+            reset_table,
+            window_size,
+            frame_size,
+            frames_per_reset,
+            cache,
+        })
+    }
+
+    /// The uncompressed length of the section.
+    pub fn len(&self) -> u64 {
+        self.reset_table.uncompressed_length
+    }
+
+    /// The amount of uncompressed data between two LZX reset points.
+    pub fn reset_block_size(&self) -> u64 {
+        self.frame_size as u64 * self.frames_per_reset as u64
+    }
+
+    /// The cache of decompressed reset blocks.
+    pub fn cache(&self) -> &BlockCache {
+        &self.cache
+    }
+
+    /// Returns the decompressed reset block with the given index, from the cache if possible.
+    pub fn reset_block(&self, block: u64) -> Result<Arc<[u8]>, DecompressError> {
+        self.cache
+            .get_or_insert_with(block, || self.decompress_reset_block(block))
+    }
+
+    /// Reads `len` bytes of uncompressed data, starting at `offset`.
+    pub fn read(&self, offset: u64, len: u64) -> Result<Vec<u8>, DecompressError> {
+        let end = offset
+            .checked_add(len)
+            .filter(|&end| end <= self.len())
+            .ok_or_else(|| RangeOutOfBounds { offset, len }.build())?;
+        let len = usize::try_from(len).map_err(|_| RangeOutOfBounds { offset, len }.build())?;
+
+        let block_size = self.reset_block_size();
+        let mut data = Vec::with_capacity(len);
+        let mut pos = offset;
+
+        while pos < end {
+            let index = pos / block_size;
+            let block = self.reset_block(index)?;
+
+            // `pos` is inside of the section, so it is inside of the block as well
+            let block = &block[(pos - index * block_size) as usize..];
+            let take = (block.len() as u64).min(end - pos) as usize;
+            data.extend_from_slice(&block[..take]);
+            pos += take as u64;
+        }
+
+        Ok(data)
+    }
+
+    /// Decompresses the reset block with the given index, bypassing the cache.
+    pub fn decompress_reset_block(&self, block: u64) -> Result<Vec<u8>, DecompressError> {
+        let offset = block.saturating_mul(self.reset_block_size());
+        if offset >= self.len() {
+            return RangeOutOfBounds {
+                offset,
+                len: self.reset_block_size(),
+            }
+            .fail();
+        }
+
+        let len = self.reset_block_size().min(self.len() - offset);
+
+        let frame_size = self.frame_size as u64;
+        let first_frame = (offset / frame_size) as usize;
+        let end_frame = ((offset + len - 1) / frame_size) as usize + 1;
+
+        let mut data = Vec::with_capacity(len as usize);
+        self.decompress_frames(first_frame..end_frame, |frame| {
+            data.extend_from_slice(frame)
+        })?;
+
+        Ok(data)
+    }
+
+    /// Decompresses the given range of frames, passing each decompressed frame to `f`.
+    ///
+    /// The range has to start at a reset point.
+    fn decompress_frames(
+        &self,
+        frames: Range<usize>,
+        mut f: impl FnMut(&[u8]),
+    ) -> Result<(), DecompressError> {
+        debug_assert_eq!(frames.start % self.frames_per_reset, 0);
+
+        // read the compressed data of all frames at once
+        let range_of = |frame| {
+            self.reset_table
+                .frame_range(frame)
+                .filter(|range| range.end <= self.compressed.len())
+                .ok_or_else(|| FrameOutOfBounds { frame }.build())
+        };
+        let start = range_of(frames.start)?.start;
+        let end = match frames.end.checked_sub(1) {
+            Some(last) => range_of(last)?.end.max(start),
+            None => start,
+        };
+        let compressed = self
+            .compressed
+            .slice(start, end - start)
+            .ok_or_else(|| {
+                FrameOutOfBounds {
+                    frame: frames.start,
+                }
+                .build()
+            })?
+            .read()
+            .context(ReadCompressedData)?;
+
+        let mut lzxd = Lzxd::new(self.window_size);
+
+        for frame in frames {
+            if frame % self.frames_per_reset == 0 {
+                lzxd = Lzxd::new(self.window_size);
+            }
+
+            let range = range_of(frame)?;
+            if range.start < start || range.end > end {
+                return FrameOutOfBounds { frame }.fail();
+            }
+            // the range is inside of the data that was read, so it fits into a usize
+            let frame_data =
+                &compressed[(range.start - start) as usize..(range.end - start) as usize];
+
+            // the last frame is usually shorter than the others
+            let frame_start = frame as u64 * self.frame_size as u64;
+            let output_len = (self.frame_size as u64).min(self.len() - frame_start) as usize;
+
+            let data = lzxd
+                .decompress_next(frame_data, output_len)
+                .context(DecodeFrame { frame })?;
+            f(data);
+        }
+
+        Ok(())
+    }
+}
+
+fn lzxd_window_size(window_size: u32) -> Result<WindowSize, DecompressError> {
+    Ok(match window_size {
+        0x8000 => WindowSize::KB32,
+        0x1_0000 => WindowSize::KB64,
+        0x2_0000 => WindowSize::KB128,
+        0x4_0000 => WindowSize::KB256,
+        0x8_0000 => WindowSize::KB512,
+        0x10_0000 => WindowSize::MB1,
+        0x20_0000 => WindowSize::MB2,
+        0x40_0000 => WindowSize::MB4,
+        0x80_0000 => WindowSize::MB8,
+        0x100_0000 => WindowSize::MB16,
+        0x200_0000 => WindowSize::MB32,
+        _ => return UnsupportedWindowSize { window_size }.fail(),
+    })
+}
+
+#[derive(Debug, Snafu)]
+pub enum DecompressError {
+    #[snafu(display("The reset table frame size is not supported on this platform"))]
+    UnsupportedFrameSize,
+
+    #[snafu(display("Unsupported LZX window size: {:#X}", window_size))]
+    UnsupportedWindowSize { window_size: u32 },
+
+    #[snafu(display(
+        "The reset interval {:#X} is not a multiple of the frame size {:#X}",
+        reset_interval,
+        block_size
+    ))]
+    ResetIntervalNotFrameAligned {
+        reset_interval: u32,
+        block_size: u64,
+    },
+
+    #[snafu(display("The compressed data of frame {} is out of bounds", frame))]
+    FrameOutOfBounds { frame: usize },
+
+    #[snafu(display("Failed to read the compressed data:\n{}", source))]
+    ReadCompressedData { source: io::Error },
+
+    #[snafu(display("Failed to decompress frame {}: {}", frame, source))]
+    DecodeFrame {
+        frame: usize,
+        source: lzxd::DecodeFailed,
+    },
+
+    #[snafu(display(
+        "The reset table has too few frames ({}) for the uncompressed length {:#X}",
+        frames,
+        uncompressed_length
+    ))]
+    TooFewFrames {
+        frames: usize,
+        uncompressed_length: u64,
+    },
+
+    #[snafu(display(
+        "The range of {:#X} bytes at offset {:#X} is out of the section bounds",
+        len,
+        offset
+    ))]
+    RangeOutOfBounds { offset: u64, len: u64 },
+}
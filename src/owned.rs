@@ -0,0 +1,118 @@
+use std::io::{Read, Seek};
+use std::sync::Arc;
+
+use crate::{ChmFile, ParseChmFileError};
+
+/// A [`ChmFile`] that owns its data.
+///
+/// Unlike [`ChmFile`], it isn't tied to the lifetime of a buffer, so it can be stored in
+/// application state or caches and shared between threads. Cloning it is cheap, as the file
+/// data and the parsed directory are shared.
+///
+/// The data is either held in memory, or read from a seekable reader on demand, see
+/// [`from_reader`](Self::from_reader).
+#[derive(Debug, Clone)]
+pub struct OwnedChmFile {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    // borrows from `buffer`, so it has to be declared (and dropped) first
+    chm_file: ChmFile<'static>,
+    /// The whole file, or only its head if it is read from a reader
+    #[allow(dead_code)]
+    buffer: Buffer,
+}
+
+/// The memory an [`OwnedChmFile`] borrows from.
+#[derive(Debug)]
+enum Buffer {
+    Memory(Arc<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Buffer {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Memory(data) => data,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+impl OwnedChmFile {
+    /// Loads a CHM file from the given data, see [`ChmFile::load`].
+    pub fn load(data: impl Into<Arc<[u8]>>) -> Result<Self, ParseChmFileError> {
+        Self::new(Buffer::Memory(data.into()), ChmFile::load)
+    }
+
+    /// Loads a CHM file from the given data, parsing the directory chunks when they are first
+    /// accessed, see [`ChmFile::load_lazy`].
+    pub fn load_lazy(data: impl Into<Arc<[u8]>>) -> Result<Self, ParseChmFileError> {
+        Self::new(Buffer::Memory(data.into()), ChmFile::load_lazy)
+    }
+
+    /// Loads a CHM file from a seekable reader, e.g. a [`File`](std::fs::File).
+    ///
+    /// The header and the header sections, including the whole directory, are read up front.
+    /// The directory chunks are still only parsed when they're accessed, like with
+    /// [`ChmFile::load`]. The content of files, including the metadata of the MSCompressed
+    /// section, is read when it's accessed. Concurrent reads from multiple threads are
+    /// serialized.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(
+        reader: R,
+    ) -> Result<Self, ParseChmFileError> {
+        let (head, source) = ChmFile::read_head(reader)?;
+
+        Self::new(Buffer::Memory(head.into_owned().into()), |head| {
+            ChmFile::load_source(head, source)
+        })
+    }
+
+    /// Loads a CHM file from a memory map, parsing the directory chunks when they are first
+    /// accessed, see [`ChmFile::open_mmap`].
+    #[cfg(feature = "mmap")]
+    pub(crate) fn from_mmap(map: memmap2::Mmap) -> Result<Self, ParseChmFileError> {
+        Self::new(Buffer::Mapped(map), ChmFile::load_lazy)
+    }
+
+    fn new(
+        buffer: Buffer,
+        load: impl FnOnce(&'static [u8]) -> Result<ChmFile<'static>, ParseChmFileError>,
+    ) -> Result<Self, ParseChmFileError> {
+        // SAFETY: the buffer is never mutated, and its heap allocation or mapping doesn't move
+        // and lives at least as long as `Inner`, which drops the borrowing `ChmFile` first. The
+        // `'static` lifetime never leaves this module, see `chm_file`.
+        let data: &'static [u8] = unsafe { &*(buffer.as_slice() as *const [u8]) };
+        let chm_file = load(data)?;
+
+        Ok(Self {
+            inner: Arc::new(Inner { chm_file, buffer }),
+        })
+    }
+
+    /// The parsed file, borrowing from `self`.
+    pub fn chm_file(&self) -> &ChmFile<'_> {
+        let chm_file: &ChmFile<'static> = &self.inner.chm_file;
+
+        // SAFETY: shortens the lifetime of the borrowed data to the one of `self`, which keeps
+        // the buffer alive. `ChmFile` is invariant over its lifetime because of the lazily parsed
+        // directory chunks, but those are only ever filled with data borrowed from the buffer,
+        // so no shorter-lived data can end up in it.
+        unsafe { std::mem::transmute::<&ChmFile<'static>, &ChmFile<'_>>(chm_file) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<OwnedChmFile>();
+    }
+}
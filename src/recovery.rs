@@ -0,0 +1,71 @@
+use snafu::Snafu;
+
+use crate::directory_listing::listing_chunk::ParseListingChunkError;
+use crate::directory_listing::ParseDirectoryListingError;
+use crate::ParseChmFileError;
+
+/// An error that was skipped while loading a file in recovery mode.
+///
+/// See [`ChmFile::load_recovering`](crate::ChmFile::load_recovering).
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum RecoveredError {
+    #[snafu(display(
+        "The file size in header section 0 at {:#X} doesn't match the file size (file size: {:#X}, parsed: {:#X})",
+        offset,
+        expected,
+        parsed
+    ))]
+    FileSizeMismatch {
+        offset: usize,
+        expected: u64,
+        parsed: u64,
+    },
+
+    #[snafu(display(
+        "Only {} of {} directory chunks fit the directory at {:#X}, skipped the rest",
+        available,
+        chunk_count,
+        offset
+    ))]
+    DirectoryTruncated {
+        offset: usize,
+        chunk_count: u32,
+        available: u32,
+    },
+
+    #[snafu(display("Skipped the directory chunk at {:#X}:\n{}", offset, source))]
+    SkippedDirectoryChunk {
+        offset: usize,
+        source: ParseDirectoryListingError,
+    },
+
+    #[snafu(display(
+        "Skipped the rest of the listing chunk after the invalid entry at {:#X}:\n{}",
+        offset,
+        source
+    ))]
+    SkippedListingEntries {
+        offset: usize,
+        source: ParseListingChunkError,
+    },
+
+    #[snafu(display("Skipped the additional content sections:\n{}", source))]
+    SkippedContentSections {
+        #[snafu(source(from(ParseChmFileError, Box::new)))]
+        source: Box<ParseChmFileError>,
+    },
+}
+
+impl RecoveredError {
+    /// The absolute offset in the file the error was found at, if it refers to a single location.
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            Self::FileSizeMismatch { offset, .. }
+            | Self::DirectoryTruncated { offset, .. }
+            | Self::SkippedDirectoryChunk { offset, .. }
+            | Self::SkippedListingEntries { offset, .. } => Some(offset),
+            Self::SkippedContentSections { .. } => None,
+        }
+    }
+}
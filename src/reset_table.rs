@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use pahs::combinators::count;
+use pahs::slice::num::{u32_le, u64_le};
+use pahs::slice::NotEnoughDataError;
+use pahs::{try_parse, Recoverable};
+use snafu::Snafu;
+
+use crate::{Driver, Pos, Progress};
+
+pub(crate) const RESET_TABLE_FILE_NAME: &str = "::DataSpace/Storage/MSCompressed/Transform/{7FC28940-9D31-11D0-9B27-00A0C91E9C7C}/InstanceData/ResetTable";
+
+const RESET_TABLE_ENTRY_SIZE: u32 = 8;
+
+/// The LZX reset table of the MSCompressed section.
+///
+/// Contains the offsets of each compressed frame ("block") inside of the compressed data stream.
+#[derive(Debug)]
+pub struct ResetTable {
+    pub version: u32,
+    pub uncompressed_length: u64,
+    pub compressed_length: u64,
+    /// Uncompressed size of each frame, usually 0x8000
+    pub block_size: u64,
+    pub block_addresses: Vec<u64>,
+}
+
+impl ResetTable {
+    pub fn parse<'a>(pd: &mut Driver, pos: Pos<'a>) -> Progress<'a, Self, ParseResetTableError> {
+        let start = pos;
+
+        let (pos, version) = try_parse!(u32_le(pd, pos));
+        let (pos, entry_count) = try_parse!(u32_le(pd, pos));
+        let (pos, entry_size) = try_parse!(u32_le(pd, pos));
+
+        if entry_size != RESET_TABLE_ENTRY_SIZE {
+            return Progress::failure(pos, UnsupportedEntrySize { entry_size }.build());
+        }
+
+        let (pos, header_length) = try_parse!(u32_le(pd, pos));
+        let (pos, uncompressed_length) = try_parse!(u64_le(pd, pos));
+        let (pos, compressed_length) = try_parse!(u64_le(pd, pos));
+        let (pos, block_size) = try_parse!(u64_le(pd, pos));
+
+        if block_size == 0 {
+            return Progress::failure(pos, InvalidBlockSize.build());
+        }
+
+        // the entries follow directly after the header
+        let (entries_pos, _) = try_parse!(start.take(header_length as usize));
+
+        // make sure the entry count fits the data before allocating space for the entries
+        let entries_len = entry_count as usize * RESET_TABLE_ENTRY_SIZE as usize;
+        let (end, entries) = try_parse!(entries_pos.take(entries_len));
+        let entries_pos = Pos {
+            s: entries,
+            ..entries_pos
+        };
+
+        let (_, block_addresses) = try_parse!(count(entry_count as usize, u64_le)(pd, entries_pos));
+
+        Progress::success(
+            end,
+            Self {
+                version,
+                uncompressed_length,
+                compressed_length,
+                block_size,
+                block_addresses,
+            },
+        )
+    }
+
+    /// Returns the range of the compressed data of the frame with the given index, relative to
+    /// the start of the compressed data.
+    pub fn frame_range(&self, frame: usize) -> Option<Range<u64>> {
+        let start = *self.block_addresses.get(frame)?;
+        let end = self
+            .block_addresses
+            .get(frame + 1)
+            .copied()
+            .unwrap_or(self.compressed_length);
+
+        if start > end {
+            return None;
+        }
+
+        Some(start..end)
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseResetTableError {
+    #[snafu(display("Not enough data in the input"))]
+    NotEnoughData,
+
+    #[snafu(display("Unsupported reset table entry size: {}", entry_size))]
+    UnsupportedEntrySize { entry_size: u32 },
+
+    #[snafu(display("The reset table block size must not be 0"))]
+    InvalidBlockSize,
+}
+
+impl From<NotEnoughDataError> for ParseResetTableError {
+    fn from(_: NotEnoughDataError) -> Self {
+        NotEnoughData.build()
+    }
+}
+
+impl Recoverable for ParseResetTableError {
+    fn recoverable(&self) -> bool {
+        match self {
+            Self::NotEnoughData => true,
+            Self::UnsupportedEntrySize { .. } => false,
+            Self::InvalidBlockSize => false,
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use crate::sync;
+
+/// Where the data of a CHM file is read from.
+#[derive(Clone)]
+pub(crate) enum Source<'a> {
+    /// The whole file, in memory
+    Slice(&'a [u8]),
+    /// A seekable reader, of which only the requested ranges are read
+    Reader(Arc<dyn ReadAt + 'a>),
+}
+
+impl<'a> Source<'a> {
+    /// Wraps a reader, so that it can be shared by all parts of a parsed file.
+    pub fn from_reader<R: Read + Seek + Send + 'a>(mut reader: R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+
+        Ok(Self::Reader(Arc::new(SeekReader {
+            reader: Mutex::new(reader),
+            len,
+        })))
+    }
+
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Slice(data) => data.len() as u64,
+            Self::Reader(reader) => reader.len(),
+        }
+    }
+}
+
+impl fmt::Debug for Source<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Slice(data) => f.debug_tuple("Slice").field(&data.len()).finish(),
+            Self::Reader(reader) => f.debug_tuple("Reader").field(&reader.len()).finish(),
+        }
+    }
+}
+
+/// Random access to the bytes of a file, from multiple threads.
+pub(crate) trait ReadAt: Send + Sync {
+    fn len(&self) -> u64;
+
+    /// Fills `buf` with the data starting at `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+}
+
+struct SeekReader<R> {
+    reader: Mutex<R>,
+    len: u64,
+}
+
+impl<R: Read + Seek + Send> ReadAt for SeekReader<R> {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        // the position is set before every read, so a panic during a read can't leave the reader
+        // in an inconsistent state
+        let mut reader = sync::lock(&self.reader);
+
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(buf)
+    }
+}
+
+/// A range of bytes of a [`Source`].
+#[derive(Debug, Clone)]
+pub(crate) struct Region<'a> {
+    source: Source<'a>,
+    offset: u64,
+    len: u64,
+}
+
+impl<'a> Region<'a> {
+    /// The whole source.
+    pub fn new(source: Source<'a>) -> Self {
+        Self {
+            len: source.len(),
+            source,
+            offset: 0,
+        }
+    }
+
+    /// The absolute offset of the region in the source.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The `len` bytes starting at `offset`, relative to the start of this region.
+    ///
+    /// Returns `None` if the range is out of the bounds of this region.
+    pub fn slice(&self, offset: u64, len: u64) -> Option<Self> {
+        let end = offset.checked_add(len)?;
+        if end > self.len {
+            return None;
+        }
+
+        Some(Self {
+            source: self.source.clone(),
+            offset: self.offset + offset,
+            len,
+        })
+    }
+
+    /// The part of this region starting at `offset`, relative to the start of this region.
+    pub fn slice_from(&self, offset: u64) -> Option<Self> {
+        self.slice(offset, self.len.checked_sub(offset)?)
+    }
+
+    /// Reads the whole region. Regions of in-memory files are borrowed.
+    pub fn read(&self) -> io::Result<Cow<'a, [u8]>> {
+        match &self.source {
+            // regions never exceed the bounds of their source, so the range fits into a usize
+            Source::Slice(data) => Ok(Cow::Borrowed(
+                &data[self.offset as usize..(self.offset + self.len) as usize],
+            )),
+            Source::Reader(reader) => {
+                let len = usize::try_from(self.len).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "region too large to read")
+                })?;
+
+                let mut buf = vec![0; len];
+                reader.read_at(self.offset, &mut buf)?;
+                Ok(Cow::Owned(buf))
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Region<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        Self::new(Source::Slice(data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn it_reads_regions_of_readers_and_slices() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        let slice = Region::from(&data[..]);
+        let reader = Region::new(Source::from_reader(Cursor::new(data.clone())).unwrap());
+
+        for region in &[slice, reader] {
+            assert_eq!(region.len(), 256);
+
+            let part = region.slice(0x10, 0x20).unwrap();
+            assert_eq!(part.offset(), 0x10);
+            assert_eq!(&*part.read().unwrap(), &data[0x10..0x30]);
+
+            let part = part.slice_from(0x18).unwrap();
+            assert_eq!(&*part.read().unwrap(), &data[0x28..0x30]);
+
+            assert!(region.slice(0xF0, 0x11).is_none());
+            assert!(region.slice_from(0x101).is_none());
+            assert!(region.slice(u64::MAX, 2).is_none());
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use pahs::slice::num::u64_le;
+use pahs::slice::NotEnoughDataError;
+use pahs::{try_parse, Recoverable};
+use snafu::Snafu;
+
+use crate::{Driver, Pos, Progress};
+
+pub(crate) const SPAN_INFO_FILE_NAME: &str = "::DataSpace/Storage/MSCompressed/SpanInfo";
+
+/// The span info of the MSCompressed section, containing its uncompressed length.
+#[derive(Debug)]
+pub struct SpanInfo {
+    pub uncompressed_length: u64,
+}
+
+impl SpanInfo {
+    pub fn parse<'a>(pd: &mut Driver, pos: Pos<'a>) -> Progress<'a, Self, ParseSpanInfoError> {
+        let (pos, uncompressed_length) = try_parse!(u64_le(pd, pos));
+
+        Progress::success(
+            pos,
+            Self {
+                uncompressed_length,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseSpanInfoError {
+    #[snafu(display("Not enough data in the input"))]
+    NotEnoughData,
+}
+
+impl From<NotEnoughDataError> for ParseSpanInfoError {
+    fn from(_: NotEnoughDataError) -> Self {
+        NotEnoughData.build()
+    }
+}
+
+impl Recoverable for ParseSpanInfoError {
+    fn recoverable(&self) -> bool {
+        true
+    }
+}
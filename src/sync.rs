@@ -0,0 +1,11 @@
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks the mutex, ignoring whether another thread panicked while holding it.
+///
+/// Only for data that is consistent between all operations on it, so that a panic can't leave
+/// it in an inconsistent state.
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
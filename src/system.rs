@@ -0,0 +1,261 @@
+use std::convert::TryInto;
+
+use pahs::slice::num::{u16_le, u32_le};
+use pahs::slice::NotEnoughDataError;
+use pahs::{try_parse, Recoverable};
+use pahs_snafu::ProgressSnafuExt;
+use snafu::{ResultExt, Snafu};
+
+use crate::{ChmFile, Driver, Pos, Progress, ReadError};
+
+pub(crate) const SYSTEM_FILE_NAME: &str = "/#SYSTEM";
+
+/// The metadata of a help file, parsed from `/#SYSTEM`.
+///
+/// Strings are stored in the ANSI code page of the language given by `lcid`. They are decoded as
+/// UTF-8, replacing invalid sequences, so non-ASCII text may not survive unless the file was
+/// compiled with a UTF-8 code page.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct System {
+    pub version: u32,
+    /// The name of the table of contents file (`.hhc`)
+    pub contents_file: Option<String>,
+    /// The name of the index file (`.hhk`)
+    pub index_file: Option<String>,
+    /// The topic shown when the help file is opened
+    pub default_topic: Option<String>,
+    pub title: Option<String>,
+    /// The language of the help file
+    pub lcid: Option<u32>,
+    /// Whether the strings of the help file use a double-byte character set
+    pub dbcs: Option<bool>,
+    pub full_text_search: Option<bool>,
+    /// The time of compilation, as a Windows `FILETIME`: the number of 100-nanosecond intervals
+    /// since 1601-01-01 (UTC)
+    pub timestamp: Option<u64>,
+    /// The name the file was compiled as, without the extension
+    pub compiled_file: Option<String>,
+    /// Present if the file has a binary index, stored in `/$WWKeywordLinks/`
+    pub binary_index: Option<u32>,
+    /// The version of the compiler, e.g. `HHA Version 4.74.8702`
+    pub compiler_version: Option<String>,
+    /// Another timestamp of the compilation, whose format is unknown
+    pub compilation_timestamp: Option<u32>,
+    /// Present if the file has a binary table of contents, stored in `/#TOCIDX`
+    pub binary_toc: Option<u32>,
+    /// The number of information types
+    pub information_type_count: Option<u32>,
+    /// A copy of the header of the index, `/#IDXHDR`
+    pub index_header: Option<Vec<u8>>,
+    /// Data only found in files of Microsoft Office, whose format is unknown
+    pub office_data: Option<Vec<u8>>,
+    /// A checksum of the information types
+    pub information_type_checksum: Option<u32>,
+    /// The name of the window type used by default
+    pub default_window: Option<String>,
+    /// The default font, as `name,size,charset`
+    pub default_font: Option<String>,
+    /// The records with codes that aren't parsed, in the order they are stored in the file
+    pub unknown_records: Vec<SystemRecord>,
+}
+
+/// A record of `/#SYSTEM` that isn't parsed into a field of [`System`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemRecord {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+impl System {
+    /// Reads and parses `/#SYSTEM` of the given file, see [`ChmFile::system`].
+    pub(crate) fn read(chm_file: &ChmFile<'_>) -> Result<Self, ReadSystemError> {
+        let data = chm_file
+            .read_file(SYSTEM_FILE_NAME)
+            .context(ReadSystemFile)?;
+
+        let pd = &mut Driver::with_state(Default::default());
+        let (_, system) = Self::parse(pd, Pos::new(&data))
+            .snafu(|pos| ParseSystemFile { offset: pos.offset })
+            .finish();
+        system
+    }
+
+    pub fn parse<'a>(pd: &mut Driver, pos: Pos<'a>) -> Progress<'a, Self, ParseSystemError> {
+        let (mut pos, version) = try_parse!(u32_le(pd, pos));
+
+        let mut system = Self {
+            version,
+            ..Self::default()
+        };
+
+        while !pos.s.is_empty() {
+            let (record_pos, code) = try_parse!(u16_le(pd, pos));
+            let (record_pos, length) = try_parse!(u16_le(pd, record_pos));
+            let (next, data) = try_parse!(record_pos.take(usize::from(length)));
+
+            match code {
+                0 => system.contents_file = Some(null_terminated_string(data)),
+                1 => system.index_file = Some(null_terminated_string(data)),
+                2 => system.default_topic = Some(null_terminated_string(data)),
+                3 => system.title = Some(null_terminated_string(data)),
+                4 => system.parse_language_record(data),
+                5 => system.default_window = Some(null_terminated_string(data)),
+                6 => system.compiled_file = Some(null_terminated_string(data)),
+                7 => system.binary_index = dword_at(data, 0),
+                9 => system.compiler_version = Some(null_terminated_string(data)),
+                10 => system.compilation_timestamp = dword_at(data, 0),
+                11 => system.binary_toc = dword_at(data, 0),
+                12 => system.information_type_count = dword_at(data, 0),
+                13 => system.index_header = Some(data.to_vec()),
+                14 => system.office_data = Some(data.to_vec()),
+                15 => system.information_type_checksum = dword_at(data, 0),
+                16 => system.default_font = Some(null_terminated_string(data)),
+                _ => system.unknown_records.push(SystemRecord {
+                    code,
+                    data: data.to_vec(),
+                }),
+            }
+
+            pos = next;
+        }
+
+        Progress::success(pos, system)
+    }
+
+    /// Parses the record containing the language, the flags and the timestamp of the file.
+    ///
+    /// Older compilers write shorter records, so the fields missing from the record are left
+    /// out.
+    fn parse_language_record(&mut self, data: &[u8]) {
+        self.lcid = dword_at(data, 0);
+        self.dbcs = dword_at(data, 4).map(|dbcs| dbcs != 0);
+        self.full_text_search = dword_at(data, 8).map(|full_text_search| full_text_search != 0);
+        // followed by whether the file has KLinks and ALinks
+        self.timestamp = data
+            .get(20..28)
+            .map(|timestamp| u64::from_le_bytes(timestamp.try_into().unwrap()));
+    }
+}
+
+/// Decodes the little endian DWORD at `offset`, if the data is long enough.
+fn dword_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|dword| u32::from_le_bytes(dword.try_into().unwrap()))
+}
+
+/// Decodes a null-terminated string.
+pub(crate) fn null_terminated_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseSystemError {
+    #[snafu(display("Not enough data in the input"))]
+    NotEnoughData,
+}
+
+impl From<NotEnoughDataError> for ParseSystemError {
+    fn from(_: NotEnoughDataError) -> Self {
+        NotEnoughData.build()
+    }
+}
+
+impl Recoverable for ParseSystemError {
+    fn recoverable(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ReadSystemError {
+    #[snafu(display("Failed to read `{}`:\n{}", SYSTEM_FILE_NAME, source))]
+    ReadSystemFile { source: ReadError },
+
+    #[snafu(display("Failed to parse `{}` at {:#X}:\n{}", SYSTEM_FILE_NAME, offset, source))]
+    ParseSystemFile {
+        offset: usize,
+        source: ParseSystemError,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(code: u16, data: &[u8]) -> Vec<u8> {
+        let mut record = code.to_le_bytes().to_vec();
+        record.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        record.extend_from_slice(data);
+        record
+    }
+
+    #[test]
+    fn it_parses_system_records() {
+        let mut language = Vec::new();
+        for dword in &[0x409u32, 0, 1, 0, 1] {
+            language.extend_from_slice(&dword.to_le_bytes());
+        }
+        language.extend_from_slice(&0x01D0_0000_0000_0000u64.to_le_bytes());
+        language.extend_from_slice(&[0; 8]);
+
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend(record(3, b"Test Help\0"));
+        data.extend(record(2, b"html/index.htm\0"));
+        data.extend(record(4, &language));
+        data.extend(record(9, b"HHA Version 4.74.8702\0"));
+        data.extend(record(6, b"test\0"));
+        data.extend(record(7, &1u32.to_le_bytes()));
+        data.extend(record(11, &2u32.to_le_bytes()));
+        data.extend(record(13, &[1, 2, 3]));
+        data.extend(record(16, b"Arial,8,0\0"));
+        data.extend(record(8, &[0; 4]));
+
+        let pd = &mut Driver::with_state(Default::default());
+        let system = System::parse(pd, Pos::new(&data)).finish().1.unwrap();
+
+        assert_eq!(
+            system,
+            System {
+                version: 3,
+                default_topic: Some("html/index.htm".to_string()),
+                title: Some("Test Help".to_string()),
+                lcid: Some(0x409),
+                dbcs: Some(false),
+                full_text_search: Some(true),
+                timestamp: Some(0x01D0_0000_0000_0000),
+                compiler_version: Some("HHA Version 4.74.8702".to_string()),
+                compiled_file: Some("test".to_string()),
+                binary_index: Some(1),
+                binary_toc: Some(2),
+                index_header: Some(vec![1, 2, 3]),
+                default_font: Some("Arial,8,0".to_string()),
+                unknown_records: vec![SystemRecord {
+                    code: 8,
+                    data: vec![0; 4]
+                }],
+                ..System::default()
+            }
+        );
+
+        // a truncated language record
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend(record(4, &language[..0x10]));
+        let system = System::parse(pd, Pos::new(&data)).finish().1.unwrap();
+        assert_eq!(
+            system,
+            System {
+                version: 3,
+                lcid: Some(0x409),
+                dbcs: Some(false),
+                full_text_search: Some(true),
+                ..System::default()
+            }
+        );
+
+        // a truncated record header
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend(&record(3, b"Test Help\0")[..6]);
+        assert!(System::parse(pd, Pos::new(&data)).finish().1.is_err());
+    }
+}
@@ -0,0 +1,500 @@
+//! Builds small CHM files and directories for tests.
+
+use hex_literal::hex;
+
+const HEADER_LEN: usize = 0x60;
+const HEADER_SECTION_0_LEN: usize = 0x18;
+/// The length of the directory header, which is followed by the first chunk.
+pub(crate) const DIRECTORY_HEADER_LEN: usize = 0x54;
+const CHUNK_SIZE: usize = 0x1000;
+/// The quickref density of the directory header.
+pub(crate) const QUICKREF_DENSITY: u32 = 2;
+const LISTING_CHUNK_HEADER_LEN: usize = 0x14;
+const INDEX_CHUNK_HEADER_LEN: usize = 0x8;
+const FRAME_SIZE: usize = 0x8000;
+
+/// The language ID of the header.
+pub(crate) const LANGUAGE_ID: u32 = 0x409;
+
+/// The offset of the directory, which follows the header and header section 0.
+pub(crate) const DIRECTORY_OFFSET: usize = HEADER_LEN + HEADER_SECTION_0_LEN;
+
+/// A CHM file with a single listing chunk, containing the files added to it.
+///
+/// If any files are added to the MSCompressed section, they are stored in LZX uncompressed
+/// blocks, one per frame.
+pub(crate) struct TestFile {
+    /// The language ID of the directory header
+    pub directory_language_id: u32,
+    /// Store 0 as the offset of content section 0, with the files placed relative to the
+    /// position that is assumed in that case
+    pub zero_content_section_0_offset: bool,
+    /// The file size stored in header section 0, instead of the actual one
+    pub file_size: Option<u64>,
+    /// The LZX reset interval, in frames
+    pub reset_interval: u32,
+    /// The uncompressed length stored in the span info, instead of the actual one
+    pub span_info_length: Option<u64>,
+    files: Vec<(String, Vec<u8>)>,
+    compressed_files: Vec<(String, Vec<u8>)>,
+    /// Entries that don't refer to any added data, as (name, content section, offset, length)
+    entries: Vec<(String, u64, u64, u64)>,
+}
+
+impl TestFile {
+    pub fn new() -> Self {
+        Self {
+            directory_language_id: LANGUAGE_ID,
+            zero_content_section_0_offset: false,
+            file_size: None,
+            reset_interval: 2,
+            span_info_length: None,
+            files: Vec::new(),
+            compressed_files: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a file to the uncompressed content section.
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> &mut Self {
+        self.files.push((name.to_string(), data.to_vec()));
+        self
+    }
+
+    /// Adds a file to the MSCompressed section.
+    pub fn add_compressed_file(&mut self, name: &str, data: &[u8]) -> &mut Self {
+        self.compressed_files
+            .push((name.to_string(), data.to_vec()));
+        self
+    }
+
+    /// Adds an entry without any data of its own.
+    pub fn add_entry(
+        &mut self,
+        name: &str,
+        content_section: u64,
+        offset: u64,
+        length: u64,
+    ) -> &mut Self {
+        self.entries
+            .push((name.to_string(), content_section, offset, length));
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut entries = self.entries.clone();
+        let mut files = self.files.clone();
+
+        if !self.compressed_files.is_empty() {
+            let mut section = Vec::new();
+            for (name, data) in &self.compressed_files {
+                entries.push((name.clone(), 1, section.len() as u64, data.len() as u64));
+                section.extend_from_slice(data);
+            }
+
+            for (name, data) in self.ms_compressed_files(&section) {
+                files.push((name.to_string(), data));
+            }
+        }
+        files.push(("::DataSpace/NameList".to_string(), self.name_list()));
+
+        let directory_len = DIRECTORY_HEADER_LEN + CHUNK_SIZE;
+        let content_section_0_offset = DIRECTORY_OFFSET + directory_len;
+        // the offset assumed by the parser if the header specifies 0
+        let content_base = if self.zero_content_section_0_offset {
+            HEADER_LEN
+        } else {
+            content_section_0_offset
+        };
+
+        let mut content = Vec::new();
+        for (name, data) in &files {
+            let offset = content_section_0_offset - content_base + content.len();
+            entries.push((name.clone(), 0, offset as u64, data.len() as u64));
+            content.extend_from_slice(data);
+        }
+
+        let file_len = (content_section_0_offset + content.len()) as u64;
+
+        entries.sort_by_key(|(name, ..)| name.to_ascii_lowercase());
+        let mut directory = TestDirectory::new();
+        directory.language_id = self.directory_language_id;
+        directory.add_chunk(TestChunk::Listing(entries));
+
+        let mut data = self.header(directory_len, content_section_0_offset);
+        data.extend(header_section_0(self.file_size.unwrap_or(file_len)));
+        data.extend(directory.build());
+        data.extend(content);
+        data
+    }
+
+    fn header(&self, directory_len: usize, content_section_0_offset: usize) -> Vec<u8> {
+        let mut data = b"ITSF".to_vec();
+        for value in &[3, HEADER_LEN as u32, 1, 0x1234_5678, LANGUAGE_ID] {
+            data.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        data.extend_from_slice(&hex!("10FD017CAA7BD0119E0C00A0C922E6EC"));
+        data.extend_from_slice(&hex!("11FD017CAA7BD0119E0C00A0C922E6EC"));
+
+        let offset_content_section_0 = if self.zero_content_section_0_offset {
+            0
+        } else {
+            content_section_0_offset
+        };
+        for value in &[
+            HEADER_LEN,
+            HEADER_SECTION_0_LEN,
+            DIRECTORY_OFFSET,
+            directory_len,
+            offset_content_section_0,
+        ] {
+            data.extend_from_slice(&u64::to_le_bytes(*value as u64));
+        }
+        data
+    }
+
+    fn name_list(&self) -> Vec<u8> {
+        let mut names = vec!["Uncompressed"];
+        if !self.compressed_files.is_empty() {
+            names.push("MSCompressed");
+        }
+
+        let mut data = (names.len() as u16).to_le_bytes().to_vec();
+        for name in names {
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            for c in name.encode_utf16().chain(Some(0)) {
+                data.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+
+        // the length in words, including the length itself
+        let mut name_list = (data.len() as u16 / 2 + 1).to_le_bytes().to_vec();
+        name_list.extend(data);
+        name_list
+    }
+
+    /// Builds the internal files of the MSCompressed section with the given uncompressed data.
+    fn ms_compressed_files(&self, section: &[u8]) -> Vec<(&'static str, Vec<u8>)> {
+        let frames_per_reset = self.reset_interval.max(1) as usize;
+        let (content, frame_addresses) = lzx_uncompressed_blocks(section, frames_per_reset);
+
+        let mut control_data = 6u32.to_le_bytes().to_vec();
+        control_data.extend_from_slice(b"LZXC");
+        // version 2, sizes in frames: reset interval, window size, cache size
+        for value in &[2, self.reset_interval, 2, 2, 0] {
+            control_data.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+
+        let mut reset_table = Vec::new();
+        for value in &[2, frame_addresses.len() as u32, 8, 0x28] {
+            reset_table.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        for value in [
+            section.len() as u64,
+            content.len() as u64,
+            FRAME_SIZE as u64,
+        ]
+        .iter()
+        .chain(&frame_addresses)
+        {
+            reset_table.extend_from_slice(&u64::to_le_bytes(*value));
+        }
+
+        let span_info = self
+            .span_info_length
+            .unwrap_or(section.len() as u64)
+            .to_le_bytes()
+            .to_vec();
+
+        vec![
+            ("::DataSpace/Storage/MSCompressed/Content", content),
+            ("::DataSpace/Storage/MSCompressed/ControlData", control_data),
+            (
+                "::DataSpace/Storage/MSCompressed/Transform/{7FC28940-9D31-11D0-9B27-00A0C91E9C7C}/InstanceData/ResetTable",
+                reset_table,
+            ),
+            ("::DataSpace/Storage/MSCompressed/SpanInfo", span_info),
+        ]
+    }
+}
+
+fn header_section_0(file_size: u64) -> Vec<u8> {
+    let mut data = vec![0xFE, 0x01, 0, 0, 0, 0, 0, 0];
+    data.extend_from_slice(&file_size.to_le_bytes());
+    data.extend_from_slice(&[0; 8]);
+    data
+}
+
+/// A chunk of a [`TestDirectory`].
+pub(crate) enum TestChunk {
+    /// A listing chunk with the given entries, as (name, content section, offset, length), in
+    /// the given order
+    Listing(Vec<(String, u64, u64, u64)>),
+    /// An index chunk with the given keys, as (name, chunk number)
+    Index(Vec<(String, u32)>),
+    /// A chunk with an unknown tag
+    Invalid,
+}
+
+/// A directory with the chunks added to it, starting with its header.
+///
+/// The listing chunks are linked in the order of their chunk numbers, from the first chunk that
+/// isn't an index chunk to the last one.
+pub(crate) struct TestDirectory {
+    pub chunk_size: usize,
+    pub language_id: u32,
+    /// The index tree depth: 1 without an index, plus the number of levels of index chunks
+    pub index_tree_depth: u32,
+    pub root_index_chunk: Option<u32>,
+    /// The chunk count stored in the header, instead of the actual one
+    pub chunk_count: Option<u32>,
+    chunks: Vec<TestChunk>,
+}
+
+impl TestDirectory {
+    pub fn new() -> Self {
+        Self {
+            chunk_size: CHUNK_SIZE,
+            language_id: LANGUAGE_ID,
+            index_tree_depth: 1,
+            root_index_chunk: None,
+            chunk_count: None,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn add_chunk(&mut self, chunk: TestChunk) -> &mut Self {
+        self.chunks.push(chunk);
+        self
+    }
+
+    /// Adds a listing chunk with one-byte entries with the given names in content section 0.
+    pub fn add_listing_chunk(&mut self, names: &[&str]) -> &mut Self {
+        let entries = (0..)
+            .zip(names)
+            .map(|(offset, name)| (name.to_string(), 0, offset, 1))
+            .collect();
+        self.add_chunk(TestChunk::Listing(entries))
+    }
+
+    /// Adds an index chunk with the given keys, as (name, chunk number).
+    pub fn add_index_chunk(&mut self, keys: &[(&str, u32)]) -> &mut Self {
+        let keys = keys
+            .iter()
+            .map(|&(name, number)| (name.to_string(), number))
+            .collect();
+        self.add_chunk(TestChunk::Index(keys))
+    }
+
+    /// The offset of the given chunk, relative to the start of the directory.
+    pub fn chunk_offset(&self, number: usize) -> usize {
+        DIRECTORY_HEADER_LEN + number * self.chunk_size
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let is_listing = |chunk: &TestChunk| !matches!(chunk, TestChunk::Index(_));
+        let first_listing = self.chunks.iter().position(is_listing);
+        let last_listing = self.chunks.iter().rposition(is_listing);
+
+        let mut data = self.header(
+            first_listing.unwrap_or(0) as u32,
+            last_listing.unwrap_or(0) as u32,
+        );
+
+        for (number, chunk) in self.chunks.iter().enumerate() {
+            data.extend(match chunk {
+                TestChunk::Listing(entries) => {
+                    let previous = match first_listing {
+                        Some(first) if first < number => number as u32 - 1,
+                        _ => u32::MAX,
+                    };
+                    let next = match last_listing {
+                        Some(last) if last > number => number as u32 + 1,
+                        _ => u32::MAX,
+                    };
+                    self.listing_chunk(entries, previous, next)
+                }
+                TestChunk::Index(keys) => self.index_chunk(keys),
+                TestChunk::Invalid => {
+                    let mut chunk = b"PMGX".to_vec();
+                    chunk.resize(self.chunk_size, 0);
+                    chunk
+                }
+            });
+        }
+
+        data
+    }
+
+    fn header(&self, first_listing: u32, last_listing: u32) -> Vec<u8> {
+        let mut data = b"ITSP".to_vec();
+        for value in &[
+            1,
+            DIRECTORY_HEADER_LEN as u32,
+            0x0A,
+            self.chunk_size as u32,
+            QUICKREF_DENSITY,
+            self.index_tree_depth,
+            self.root_index_chunk.unwrap_or(u32::MAX),
+            first_listing,
+            last_listing,
+            u32::MAX,
+            self.chunk_count.unwrap_or(self.chunks.len() as u32),
+            self.language_id,
+        ] {
+            data.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        data.extend_from_slice(&hex!("6A92025D2E21D0119DF900A0C922E6EC"));
+        data.extend_from_slice(&(DIRECTORY_HEADER_LEN as u32).to_le_bytes());
+        data.extend_from_slice(&[0xFF; 12]);
+        data
+    }
+
+    fn listing_chunk(
+        &self,
+        entries: &[(String, u64, u64, u64)],
+        previous: u32,
+        next: u32,
+    ) -> Vec<u8> {
+        let entries = entries
+            .iter()
+            .map(|(name, content_section, offset, length)| {
+                let mut entry = encint(name.len() as u64);
+                entry.extend_from_slice(name.as_bytes());
+                entry.extend(encint(*content_section));
+                entry.extend(encint(*offset));
+                entry.extend(encint(*length));
+                entry
+            })
+            .collect::<Vec<_>>();
+
+        let mut header = b"PMGL".to_vec();
+        header.extend_from_slice(&self.free_space(LISTING_CHUNK_HEADER_LEN, &entries));
+        for value in &[0, previous, next] {
+            header.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+
+        self.chunk(header, &entries)
+    }
+
+    fn index_chunk(&self, keys: &[(String, u32)]) -> Vec<u8> {
+        let entries = keys
+            .iter()
+            .map(|(name, number)| {
+                let mut entry = encint(name.len() as u64);
+                entry.extend_from_slice(name.as_bytes());
+                entry.extend(encint(u64::from(*number)));
+                entry
+            })
+            .collect::<Vec<_>>();
+
+        let mut header = b"PMGI".to_vec();
+        header.extend_from_slice(&self.free_space(INDEX_CHUNK_HEADER_LEN, &entries));
+
+        self.chunk(header, &entries)
+    }
+
+    /// The length of the area after the entries, which ends with the quickref area.
+    fn free_space(&self, header_len: usize, entries: &[Vec<u8>]) -> [u8; 4] {
+        let entries_len: usize = entries.iter().map(Vec::len).sum();
+        ((self.chunk_size - header_len - entries_len) as u32).to_le_bytes()
+    }
+
+    /// Builds a chunk from its header and entries, followed by the quickref area.
+    fn chunk(&self, header: Vec<u8>, entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut chunk = header;
+        let mut entry_offsets = Vec::new();
+        let mut entries_len = 0;
+        for entry in entries {
+            entry_offsets.push(entries_len);
+            entries_len += entry.len();
+            chunk.extend_from_slice(entry);
+        }
+
+        // every `1 + (1 << density)`th entry, stored backwards from the end of the chunk
+        let interval = (1 << QUICKREF_DENSITY) + 1;
+        let mut quickref = (entries.len() as u16).to_le_bytes().to_vec();
+        for &offset in entry_offsets.iter().skip(interval).step_by(interval) {
+            let mut word = (offset as u16).to_le_bytes().to_vec();
+            word.extend(quickref);
+            quickref = word;
+        }
+
+        chunk.resize(self.chunk_size - quickref.len(), 0);
+        chunk.extend(quickref);
+        chunk
+    }
+}
+
+fn encint(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value != 0 {
+        bytes.insert(0, (value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes
+}
+
+/// Stores the data in LZX uncompressed blocks, one per frame.
+///
+/// Returns the compressed data and the offsets of the frames in it.
+fn lzx_uncompressed_blocks(data: &[u8], frames_per_reset: usize) -> (Vec<u8>, Vec<u64>) {
+    let mut compressed = Vec::new();
+    let mut frame_addresses = Vec::new();
+
+    for (i, frame) in data.chunks(FRAME_SIZE).enumerate() {
+        frame_addresses.push(compressed.len() as u64);
+
+        let mut bits = BitWriter::default();
+        if i % frames_per_reset == 0 {
+            // no E8 translation
+            bits.write(0, 1);
+        }
+        // block type: uncompressed, followed by the block size
+        bits.write(3, 3);
+        bits.write(frame.len() as u32, 24);
+        compressed.extend(bits.align());
+
+        // the repeated offsets R0, R1 and R2
+        for _ in 0..3 {
+            compressed.extend_from_slice(&1u32.to_le_bytes());
+        }
+        compressed.extend_from_slice(frame);
+        if frame.len() % 2 != 0 {
+            compressed.push(0);
+        }
+    }
+
+    (compressed, frame_addresses)
+}
+
+/// Writes bits the way LZX reads them: in 16-bit little endian words, most significant bit
+/// first.
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    word: u16,
+    len: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        for i in (0..bits).rev() {
+            self.word = (self.word << 1) | ((value >> i) & 1) as u16;
+            self.len += 1;
+            if self.len == 16 {
+                self.data.extend_from_slice(&self.word.to_le_bytes());
+                self.word = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Pads the data with 1 to 16 bits to the next word, as required before the data of an
+    /// uncompressed block.
+    fn align(mut self) -> Vec<u8> {
+        self.write(0, 16 - self.len);
+        self.data
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ops::Index;
+
+use pahs::slice::num::{u16_le, u32_le};
+use pahs::slice::NotEnoughDataError;
+use pahs::{try_parse, Recoverable};
+use pahs_snafu::ProgressSnafuExt;
+use snafu::{ResultExt, Snafu};
+
+use crate::system::null_terminated_string;
+use crate::{ChmFile, Driver, Pos, Progress, ReadError};
+
+const TOPICS_FILE_NAME: &str = "/#TOPICS";
+const STRINGS_FILE_NAME: &str = "/#STRINGS";
+const URL_TABLE_FILE_NAME: &str = "/#URLTBL";
+const URL_STRINGS_FILE_NAME: &str = "/#URLSTR";
+
+/// Marks a missing title in `#TOPICS`.
+const NO_TITLE: u32 = u32::MAX;
+
+/// Set in the flags of topics that are part of the table of contents.
+const IN_CONTENTS_FLAG: u16 = 0x4;
+
+/// The topics of a help file, parsed from `/#TOPICS`, `/#STRINGS`, `/#URLTBL` and `/#URLSTR`.
+///
+/// The binary table of contents, the binary index and the full-text search refer to topics by
+/// their number, the index in this table.
+#[derive(Debug, Clone, Default)]
+pub struct TopicTable {
+    topics: Vec<Topic>,
+    /// The numbers of the topics, by their ASCII-lowercased local path
+    by_local: HashMap<String, u32>,
+}
+
+/// A topic of a [`TopicTable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topic {
+    pub title: Option<String>,
+    /// The path of the topic relative to the root of the file, e.g. `html/topic.htm`
+    pub local: Option<String>,
+    /// Whether the topic is part of the table of contents
+    pub in_contents: bool,
+}
+
+/// The files a [`TopicTable`] refers to.
+pub(crate) struct TopicFiles<'a> {
+    pub strings: &'a [u8],
+    pub url_table: &'a [u8],
+    pub url_strings: &'a [u8],
+}
+
+impl TopicTable {
+    /// Reads and parses the topic table of the given file, see [`ChmFile::topic_table`].
+    pub(crate) fn read(chm_file: &ChmFile<'_>) -> Result<Self, ReadTopicTableError> {
+        let read = |name| chm_file.read_file(name).context(ReadTopicFile { name });
+
+        let topics = read(TOPICS_FILE_NAME)?;
+        let strings = read(STRINGS_FILE_NAME)?;
+        let url_table = read(URL_TABLE_FILE_NAME)?;
+        let url_strings = read(URL_STRINGS_FILE_NAME)?;
+        let files = TopicFiles {
+            strings: &strings,
+            url_table: &url_table,
+            url_strings: &url_strings,
+        };
+
+        let pd = &mut Driver::with_state(Default::default());
+        let (_, table) = Self::parse(pd, Pos::new(&topics), &files)
+            .snafu(|pos| ParseTopicTable { offset: pos.offset })
+            .finish();
+        table
+    }
+
+    /// Parses `#TOPICS`, resolving the titles and paths of the topics in the other files.
+    pub(crate) fn parse<'a>(
+        pd: &mut Driver,
+        mut pos: Pos<'a>,
+        files: &TopicFiles<'_>,
+    ) -> Progress<'a, Self, ParseTopicTableError> {
+        let mut table = Self::default();
+
+        while !pos.s.is_empty() {
+            let start = pos;
+            // offset into `#TOCIDX`, not needed to resolve the topic
+            let (p, _) = try_parse!(u32_le(pd, pos));
+            let (p, title_offset) = try_parse!(u32_le(pd, p));
+            let (p, url_table_offset) = try_parse!(u32_le(pd, p));
+            let (p, flags) = try_parse!(u16_le(pd, p));
+            let (p, _) = try_parse!(u16_le(pd, p));
+
+            let topic = files.resolve(title_offset, url_table_offset, flags);
+            let topic = match topic {
+                Ok(topic) => topic,
+                Err(e) => return Progress::failure(start, e),
+            };
+            table.push(topic);
+
+            pos = p;
+        }
+
+        Progress::success(pos, table)
+    }
+
+    fn push(&mut self, topic: Topic) {
+        // `#TOPICS` has 16 bytes per topic, so the number always fits
+        let number = u32::try_from(self.topics.len()).unwrap();
+
+        if let Some(local) = &topic.local {
+            // topics sharing a path are looked up as the first one
+            self.by_local
+                .entry(local.to_ascii_lowercase())
+                .or_insert(number);
+        }
+        self.topics.push(topic);
+    }
+
+    /// The number of topics.
+    pub fn len(&self) -> usize {
+        self.topics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.topics.is_empty()
+    }
+
+    /// Returns the topic with the given number.
+    pub fn get(&self, number: u32) -> Option<&Topic> {
+        self.topics.get(usize::try_from(number).ok()?)
+    }
+
+    /// Looks up a topic by its local path, e.g. `html/topic.htm` or `/html/topic.htm`.
+    ///
+    /// The path is compared ASCII case-insensitively, like entry names in
+    /// [`ChmFile::find_entry`]. Returns the number of the topic along with the topic.
+    pub fn find(&self, local: &str) -> Option<(u32, &Topic)> {
+        let local = local.strip_prefix('/').unwrap_or(local);
+        let number = *self.by_local.get(&local.to_ascii_lowercase())?;
+
+        Some((number, &self.topics[number as usize]))
+    }
+
+    /// Returns all topics along with their numbers.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Topic)> + '_ {
+        (0..).zip(&self.topics)
+    }
+}
+
+impl Index<u32> for TopicTable {
+    type Output = Topic;
+
+    fn index(&self, number: u32) -> &Topic {
+        self.get(number).expect("topic number out of bounds")
+    }
+}
+
+impl TopicFiles<'_> {
+    fn resolve(
+        &self,
+        title_offset: u32,
+        url_table_offset: u32,
+        flags: u16,
+    ) -> Result<Topic, ParseTopicTableError> {
+        let title = match title_offset {
+            NO_TITLE => None,
+            offset => Some(string_at(self.strings, STRINGS_FILE_NAME, offset)?),
+        };
+
+        // `#URLTBL` entry: a hash of the path, the topic number and the offset into `#URLSTR`
+        let url_strings_offset = usize::try_from(url_table_offset)
+            .ok()
+            .and_then(|offset| self.url_table.get(offset..offset.checked_add(12)?))
+            .map(|entry| u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]))
+            .ok_or_else(|| {
+                UrlTableEntryOutOfBounds {
+                    offset: url_table_offset,
+                }
+                .build()
+            })?;
+
+        // `#URLSTR` entry: the offset of an external URL, the offset of the frame name in
+        // `#STRINGS` and the local path
+        let local = match url_strings_offset.checked_add(8) {
+            Some(offset) => string_at(self.url_strings, URL_STRINGS_FILE_NAME, offset)?,
+            None => {
+                return StringOutOfBounds {
+                    file: URL_STRINGS_FILE_NAME,
+                    offset: url_strings_offset,
+                }
+                .fail()
+            }
+        };
+
+        Ok(Topic {
+            title,
+            local: Some(local).filter(|local| !local.is_empty()),
+            in_contents: flags & IN_CONTENTS_FLAG != 0,
+        })
+    }
+}
+
+/// Decodes the null-terminated string starting at `offset`.
+fn string_at(data: &[u8], file: &'static str, offset: u32) -> Result<String, ParseTopicTableError> {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| data.get(offset..))
+        .map(null_terminated_string)
+        .ok_or_else(|| StringOutOfBounds { file, offset }.build())
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseTopicTableError {
+    #[snafu(display("Not enough data in the input"))]
+    NotEnoughData,
+
+    #[snafu(display("The `#URLTBL` entry at {:#X} is out of bounds", offset))]
+    UrlTableEntryOutOfBounds { offset: u32 },
+
+    #[snafu(display("The string at {:#X} is out of the bounds of `{}`", offset, file))]
+    StringOutOfBounds { file: &'static str, offset: u32 },
+}
+
+impl From<NotEnoughDataError> for ParseTopicTableError {
+    fn from(_: NotEnoughDataError) -> Self {
+        NotEnoughData.build()
+    }
+}
+
+impl Recoverable for ParseTopicTableError {
+    fn recoverable(&self) -> bool {
+        use ParseTopicTableError::*;
+        match self {
+            NotEnoughData => true,
+            UrlTableEntryOutOfBounds { .. } => false,
+            StringOutOfBounds { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ReadTopicTableError {
+    #[snafu(display("Failed to read `{}`:\n{}", name, source))]
+    ReadTopicFile {
+        name: &'static str,
+        source: ReadError,
+    },
+
+    #[snafu(display("Failed to parse `{}` at {:#X}:\n{}", TOPICS_FILE_NAME, offset, source))]
+    ParseTopicTable {
+        offset: usize,
+        source: ParseTopicTableError,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn topic(title_offset: u32, url_table_offset: u32, flags: u16) -> Vec<u8> {
+        let mut topic = 0u32.to_le_bytes().to_vec();
+        topic.extend_from_slice(&title_offset.to_le_bytes());
+        topic.extend_from_slice(&url_table_offset.to_le_bytes());
+        topic.extend_from_slice(&flags.to_le_bytes());
+        topic.extend_from_slice(&0u16.to_le_bytes());
+        topic
+    }
+
+    fn url_table_entry(topic: u32, url_strings_offset: u32) -> Vec<u8> {
+        let mut entry = 0x1234_5678u32.to_le_bytes().to_vec();
+        entry.extend_from_slice(&topic.to_le_bytes());
+        entry.extend_from_slice(&url_strings_offset.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn it_resolves_topics() {
+        let strings = b"\0Introduction\0Details\0";
+        let url_strings =
+            b"\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0html/Intro.htm\0\0\0\0\0\0\0\0\0html/details.htm\0";
+
+        let mut url_table = url_table_entry(0, 8);
+        url_table.extend(url_table_entry(1, 0x1F));
+
+        let mut topics = topic(1, 0, 6);
+        topics.extend(topic(0xE, 12, 2));
+        topics.extend(topic(NO_TITLE, 0, 2));
+
+        let files = TopicFiles {
+            strings,
+            url_table: &url_table,
+            url_strings,
+        };
+        let pd = &mut Driver::with_state(Default::default());
+        let table = TopicTable::parse(pd, Pos::new(&topics), &files)
+            .finish()
+            .1
+            .unwrap();
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table[0],
+            Topic {
+                title: Some("Introduction".to_string()),
+                local: Some("html/Intro.htm".to_string()),
+                in_contents: true,
+            }
+        );
+        assert_eq!(table[1].title.as_deref(), Some("Details"));
+        assert!(!table[1].in_contents);
+        assert_eq!(table[2].title, None);
+        assert!(table.get(3).is_none());
+
+        assert_eq!(
+            table.find("/html/intro.htm").map(|(number, _)| number),
+            Some(0)
+        );
+        assert_eq!(
+            table.find("html/details.htm").map(|(number, _)| number),
+            Some(1)
+        );
+        assert!(table.find("html/missing.htm").is_none());
+
+        let mut topics = topic(1, 24, 6);
+        topics.extend(topic(0x100, 0, 6));
+        assert!(matches!(
+            TopicTable::parse(pd, Pos::new(&topics), &files).finish().1,
+            Err(ParseTopicTableError::UrlTableEntryOutOfBounds { offset: 24 })
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::chm_file::Entry;
+
+/// A hierarchical view over the flat directory listing of a CHM file.
+///
+/// Only names starting with `/` are part of the tree. Directories are the zero-length entries
+/// ending in `/`; directories that only exist implicitly (as a prefix of other names) are
+/// synthesized.
+#[derive(Debug)]
+pub struct DirectoryTree<'a> {
+    nodes: HashMap<&'a str, Node<'a>>,
+    children: BTreeMap<&'a str, BTreeSet<&'a str>>,
+}
+
+/// A file or directory in a [`DirectoryTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node<'a> {
+    File(Entry<'a>),
+    Directory(Directory<'a>),
+}
+
+/// A directory in a [`DirectoryTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Directory<'a> {
+    /// The path of the directory, including the trailing `/`
+    pub path: &'a str,
+    /// The directory listing entry, or `None` if the directory only exists implicitly
+    pub entry: Option<Entry<'a>>,
+}
+
+impl<'a> Node<'a> {
+    /// The full path of the node. Directory paths end with a `/`.
+    pub fn path(&self) -> &'a str {
+        match self {
+            Self::File(entry) => entry.name,
+            Self::Directory(directory) => directory.path,
+        }
+    }
+
+    /// The last component of the path, without a trailing `/`.
+    pub fn name(&self) -> &'a str {
+        let path = self.path();
+        let path = path.strip_suffix('/').unwrap_or(path);
+        path.rfind('/').map_or(path, |i| &path[i + 1..])
+    }
+}
+
+impl<'a> DirectoryTree<'a> {
+    pub(crate) fn new(entries: impl IntoIterator<Item = Entry<'a>>) -> Self {
+        let mut tree = Self {
+            nodes: HashMap::new(),
+            children: BTreeMap::new(),
+        };
+
+        tree.insert_directory("/", None);
+
+        for entry in entries {
+            if !entry.name.starts_with('/') {
+                continue;
+            }
+
+            if entry.name.ends_with('/') {
+                tree.insert_directory(entry.name, Some(entry));
+            } else {
+                tree.nodes.insert(entry.name, Node::File(entry));
+                tree.insert_child(entry.name);
+            }
+        }
+
+        tree
+    }
+
+    fn insert_directory(&mut self, path: &'a str, entry: Option<Entry<'a>>) {
+        if let Some(node) = self.nodes.get_mut(path) {
+            if let (Node::Directory(directory), Some(_)) = (node, entry) {
+                directory.entry = entry;
+            }
+            return;
+        }
+
+        self.nodes
+            .insert(path, Node::Directory(Directory { path, entry }));
+        self.children.entry(path).or_default();
+        self.insert_child(path);
+    }
+
+    fn insert_child(&mut self, path: &'a str) {
+        if let Some(parent) = parent_path(path) {
+            self.insert_directory(parent, None);
+            self.children.entry(parent).or_default().insert(path);
+        }
+    }
+
+    /// Returns the file or directory at the given path.
+    ///
+    /// Directories can be specified with or without the trailing `/`.
+    pub fn metadata(&self, path: &str) -> Option<Node<'a>> {
+        self.nodes
+            .get(path)
+            .or_else(|| {
+                if path.ends_with('/') {
+                    None
+                } else {
+                    self.nodes.get(format!("{}/", path).as_str())
+                }
+            })
+            .copied()
+    }
+
+    /// Returns the direct children of the directory at the given path, sorted by path.
+    ///
+    /// Returns `None` if there is no directory at the given path.
+    pub fn read_dir(&self, path: &str) -> Option<impl Iterator<Item = Node<'a>> + '_> {
+        let directory = match self.metadata(path)? {
+            Node::Directory(directory) => directory,
+            Node::File(_) => return None,
+        };
+
+        let children = self.children.get(directory.path)?;
+        Some(children.iter().map(move |path| self.nodes[path]))
+    }
+
+    /// Returns all files and directories of the tree in depth-first order, starting at the root
+    /// directory.
+    pub fn walk(&self) -> Walk<'_, 'a> {
+        Walk {
+            tree: self,
+            stack: vec!["/"],
+        }
+    }
+}
+
+/// Depth-first iterator over a [`DirectoryTree`], created by [`DirectoryTree::walk`].
+#[derive(Debug)]
+pub struct Walk<'t, 'a> {
+    tree: &'t DirectoryTree<'a>,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Iterator for Walk<'_, 'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.stack.pop()?;
+
+        if let Some(children) = self.tree.children.get(path) {
+            self.stack.extend(children.iter().rev());
+        }
+
+        Some(self.tree.nodes[path])
+    }
+}
+
+/// Returns the path of the directory containing the given path.
+fn parent_path(path: &str) -> Option<&str> {
+    let path = path.strip_suffix('/').unwrap_or(path);
+    path.rfind('/').map(|i| &path[..=i])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(name: &str) -> Entry<'_> {
+        Entry {
+            name,
+            content_section: 0,
+            offset: 0,
+            length: 0,
+        }
+    }
+
+    #[test]
+    fn it_synthesizes_implicit_directories() {
+        let tree = DirectoryTree::new(vec![
+            entry("/"),
+            entry("/html/"),
+            entry("/html/topic.htm"),
+            entry("/images/sub/logo.gif"),
+            entry("::DataSpace/NameList"),
+        ]);
+
+        let root: Vec<_> = tree.read_dir("/").unwrap().map(|n| n.path()).collect();
+        assert_eq!(root, ["/html/", "/images/"]);
+
+        match tree.metadata("/images/sub").unwrap() {
+            Node::Directory(directory) => {
+                assert_eq!(directory.path, "/images/sub/");
+                assert_eq!(directory.entry, None);
+            }
+            Node::File(_) => panic!("expected a directory"),
+        }
+
+        assert!(matches!(
+            tree.metadata("/html"),
+            Some(Node::Directory(Directory { entry: Some(_), .. }))
+        ));
+        assert!(tree.read_dir("/html/topic.htm").is_none());
+        assert!(tree.metadata("::DataSpace/NameList").is_none());
+
+        let walked: Vec<_> = tree.walk().map(|n| n.path()).collect();
+        assert_eq!(
+            walked,
+            [
+                "/",
+                "/html/",
+                "/html/topic.htm",
+                "/images/",
+                "/images/sub/",
+                "/images/sub/logo.gif"
+            ]
+        );
+    }
+}
@@ -5,7 +5,7 @@ use uuid::Uuid;
 
 use super::{Driver, Pos, Progress};
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 #[snafu(visibility = "pub(crate)")]
 pub enum ParseUuidError {
     NotEnoughData,
@@ -18,7 +18,7 @@ impl Recoverable for ParseUuidError {
     }
 }
 
-#[derive(Debug, Snafu)]
+#[derive(Debug, Clone, Snafu)]
 #[snafu(visibility = "pub(crate)")]
 pub enum ParseExactUuidError {
     #[snafu(display("Uuid parse failed:{}\n", source))]
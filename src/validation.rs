@@ -0,0 +1,81 @@
+use pahs::slice::num::u32_le;
+use pahs::slice::NotEnoughDataError;
+use pahs::try_parse;
+use snafu::Snafu;
+
+use crate::warning::UnexpectedReservedValue;
+use crate::{Driver, Pos, Progress};
+
+/// Decides how strictly reserved and unknown fields are checked while parsing.
+///
+/// The expected values are the ones HTML Help Workshop writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationPolicy {
+    /// Ignore the values of reserved and unknown fields, and accept any value in place of the
+    /// `-1` markers of the directory header
+    Lenient,
+    /// Report unexpected values of reserved and unknown fields as warnings, and require the `-1`
+    /// markers of the directory header
+    #[default]
+    Default,
+    /// Fail on unexpected values of reserved and unknown fields, and require the `-1` markers of
+    /// the directory header
+    Pedantic,
+}
+
+/// Parses a reserved or unknown dword and checks it against the expected value, according to the
+/// validation policy in the parse state.
+pub(crate) fn reserved_u32<'a, E>(
+    field: &'static str,
+    expected: u32,
+) -> impl Fn(&mut Driver, Pos<'a>) -> Progress<'a, u32, E>
+where
+    E: From<NotEnoughDataError> + From<UnexpectedReservedValueError>,
+{
+    move |pd, pos| {
+        let (next, value) = try_parse!(u32_le(pd, pos));
+
+        if value != expected {
+            let offset = pos.offset;
+
+            match pd.state.validation {
+                ValidationPolicy::Lenient => {}
+                ValidationPolicy::Default => pd.state.warnings.push(
+                    UnexpectedReservedValue {
+                        offset,
+                        field,
+                        value,
+                        expected,
+                    }
+                    .build(),
+                ),
+                ValidationPolicy::Pedantic => {
+                    let error = UnexpectedReservedValueError {
+                        offset,
+                        field,
+                        value,
+                        expected,
+                    };
+                    return Progress::failure(pos, error.into());
+                }
+            }
+        }
+
+        Progress::success(next, value)
+    }
+}
+
+#[derive(Debug, Clone, Snafu)]
+#[snafu(display(
+    "Unexpected value {:#X} of the field `{}` at {:#X}, expected {:#X}",
+    value,
+    field,
+    offset,
+    expected
+))]
+pub struct UnexpectedReservedValueError {
+    offset: usize,
+    field: &'static str,
+    value: u32,
+    expected: u32,
+}
@@ -0,0 +1,138 @@
+use snafu::Snafu;
+
+/// A quirk or inconsistency in a CHM file that doesn't prevent parsing it.
+///
+/// Every warning carries the absolute offset in the file it was found at.
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum ParseWarning {
+    #[snafu(display(
+        "The content section 0 offset in the header at {:#X} is 0, assuming the usual position",
+        offset
+    ))]
+    ZeroContentSection0Offset { offset: usize },
+
+    #[snafu(display(
+        "The language ID of the directory header at {:#X} ({:#X}) doesn't match the one of the header ({:#X})",
+        offset,
+        directory_language_id,
+        header_language_id
+    ))]
+    LanguageIdMismatch {
+        offset: usize,
+        header_language_id: u32,
+        directory_language_id: u32,
+    },
+
+    #[snafu(display(
+        "The field `{}` at {:#X} is {:#X} instead of {:#X}",
+        field,
+        offset,
+        value,
+        expected
+    ))]
+    UnexpectedReservedValue {
+        offset: usize,
+        field: &'static str,
+        value: u32,
+        expected: u32,
+    },
+
+    #[snafu(display("The quickref area at {:#X} is too short", offset))]
+    QuickrefTooShort { offset: usize },
+
+    #[snafu(display(
+        "The quickref area at {:#X} specifies {} entries, but the chunk has {}",
+        offset,
+        count,
+        entries
+    ))]
+    QuickrefEntryCountMismatch {
+        offset: usize,
+        count: usize,
+        entries: usize,
+    },
+
+    #[snafu(display(
+        "The quickref area at {:#X} doesn't point at the start of entry {}",
+        offset,
+        entry
+    ))]
+    QuickrefOffsetMismatch { offset: usize, entry: usize },
+
+    #[snafu(display(
+        "The PMGL chain links to the chunk at {:#X}, which isn't a listing chunk",
+        offset
+    ))]
+    ChainLinksToInvalidChunk { offset: usize },
+
+    #[snafu(display("The PMGL chain contains a cycle at the chunk at {:#X}", offset))]
+    ChainCycle { offset: usize },
+
+    #[snafu(display(
+        "The previous chunk of the listing chunk at {:#X} doesn't match the PMGL chain",
+        offset
+    ))]
+    ChainBackLinkMismatch { offset: usize },
+
+    #[snafu(display(
+        "The PMGL chain ends at the chunk at {:#X}, which isn't the last listing chunk",
+        offset
+    ))]
+    ChainEndMismatch { offset: usize },
+
+    #[snafu(display("The listing chunk at {:#X} isn't part of the PMGL chain", offset))]
+    ChunkNotInChain { offset: usize },
+
+    #[snafu(display("The entries of the listing chunk at {:#X} are not sorted", offset))]
+    EntriesNotSorted { offset: usize },
+
+    #[snafu(display(
+        "An entry of the index chunk at {:#X} refers to a chunk that doesn't exist",
+        offset
+    ))]
+    IndexKeyChunkMissing { offset: usize },
+
+    #[snafu(display(
+        "A key of the index chunk at {:#X} doesn't match the first name of the chunk it refers to",
+        offset
+    ))]
+    IndexKeyMismatch { offset: usize },
+
+    #[snafu(display(
+        "The file `{}` ends at {:#X}, after the end of the MSCompressed section ({:#X}) given by the reset table at {:#X}",
+        name,
+        end,
+        section_length,
+        offset
+    ))]
+    CompressedFileOutOfBounds {
+        offset: usize,
+        name: String,
+        end: u64,
+        section_length: u64,
+    },
+}
+
+impl ParseWarning {
+    /// The absolute offset in the file the warning refers to.
+    pub fn offset(&self) -> usize {
+        match *self {
+            Self::ZeroContentSection0Offset { offset }
+            | Self::LanguageIdMismatch { offset, .. }
+            | Self::UnexpectedReservedValue { offset, .. }
+            | Self::QuickrefTooShort { offset }
+            | Self::QuickrefEntryCountMismatch { offset, .. }
+            | Self::QuickrefOffsetMismatch { offset, .. }
+            | Self::ChainLinksToInvalidChunk { offset }
+            | Self::ChainCycle { offset }
+            | Self::ChainBackLinkMismatch { offset }
+            | Self::ChainEndMismatch { offset }
+            | Self::ChunkNotInChain { offset }
+            | Self::EntriesNotSorted { offset }
+            | Self::IndexKeyChunkMissing { offset }
+            | Self::IndexKeyMismatch { offset }
+            | Self::CompressedFileOutOfBounds { offset, .. } => offset,
+        }
+    }
+}
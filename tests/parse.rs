@@ -1,7 +1,7 @@
 #![feature(or_patterns)]
 #![feature(bindings_after_at)]
 
-use chmparse::{ChmFile, ParseChmFileError, ParseChmFileHeadError};
+use chmparse::{ChmFile, OwnedChmFile, ParseChmFileError, ParseChmFileHeadError};
 
 const TEST_FILES: &[&str] = &[
     "test-files/7-zip.chm",
@@ -16,7 +16,8 @@ fn it_parses_test_files() {
         println!("File: {}", file);
         let content = std::fs::read(file).unwrap();
 
-        match &ChmFile::load(&content) {
+        // parses the whole directory, unlike `ChmFile::load`
+        match &ChmFile::load_with_diagnostics(&content) {
             Err(
                 e
                 @
@@ -69,6 +70,184 @@ fn it_parses_test_files() {
     }
 }
 
+#[test]
+fn it_reads_all_entries() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let content = std::fs::read(file).unwrap();
+        let chm_file = ChmFile::load(&content).unwrap();
+
+        for entry in chm_file.entries().map(Result::unwrap) {
+            let data = chm_file.read_file(entry.name).unwrap();
+            assert_eq!(data.len() as u64, entry.length, "{}", entry.name);
+        }
+    }
+}
+
+#[test]
+fn it_shares_owned_files_between_threads() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let owned = OwnedChmFile::load(std::fs::read(file).unwrap()).unwrap();
+
+        let shared = owned.clone();
+        let names: Vec<String> = std::thread::spawn(move || {
+            let chm_file = shared.chm_file();
+            chm_file
+                .entries()
+                .map(|entry| entry.unwrap().name.to_owned())
+                .collect()
+        })
+        .join()
+        .unwrap();
+
+        let chm_file = owned.chm_file();
+        assert_eq!(names.len(), chm_file.entries().count());
+        for name in &names {
+            assert!(chm_file.read_file(name).is_ok(), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn it_reads_entries_from_readers() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let owned = OwnedChmFile::from_reader(std::fs::File::open(file).unwrap()).unwrap();
+        let from_reader = owned.chm_file();
+
+        let content = std::fs::read(file).unwrap();
+        let chm_file = ChmFile::load(&content).unwrap();
+
+        assert!(chm_file
+            .entries()
+            .map(Result::unwrap)
+            .eq(from_reader.entries().map(Result::unwrap)));
+        for entry in from_reader.entries().map(Result::unwrap) {
+            let data = from_reader.read_file(entry.name).unwrap();
+            assert_eq!(data, chm_file.read_file(entry.name).unwrap(), "{}", entry.name);
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn it_reads_entries_through_memory_maps() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let owned = ChmFile::open_mmap(file).unwrap();
+        let chm_file = owned.chm_file();
+
+        for entry in chm_file.entries().map(Result::unwrap) {
+            let data = chm_file.read_file(entry.name).unwrap();
+            assert_eq!(data.len() as u64, entry.length, "{}", entry.name);
+        }
+    }
+
+    assert!(matches!(
+        ChmFile::open_mmap("test-files/missing.chm"),
+        Err(chmparse::OpenMmapError::MapFile { .. })
+    ));
+}
+
+#[test]
+fn it_caches_decompressed_blocks() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let content = std::fs::read(file).unwrap();
+        let chm_file = ChmFile::load(&content).unwrap();
+
+        // a file inside of a single reset block, so that it fits into the cache
+        let block_size = match chm_file.control_data() {
+            Some(control_data) => u64::from(control_data.reset_interval),
+            None => continue,
+        };
+        let entry = chm_file
+            .entries_in_section(1)
+            .map(Result::unwrap)
+            .find(|entry| {
+                entry.length > 0
+                    && entry.offset / block_size == (entry.offset + entry.length - 1) / block_size
+            })
+            .unwrap();
+
+        let data = chm_file.read_file(entry.name).unwrap();
+        let stats = chm_file.block_cache_stats().unwrap();
+        assert!(stats.misses > 0);
+
+        assert_eq!(chm_file.read_file(entry.name).unwrap(), data);
+        assert!(chm_file.block_cache_stats().unwrap().hits > stats.hits);
+
+        chm_file.set_block_cache_capacity(0);
+        assert_eq!(chm_file.block_cache_stats().unwrap().len, 0);
+    }
+}
+
+#[test]
+fn it_extracts_all_files() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let content = std::fs::read(file).unwrap();
+        let chm_file = ChmFile::load(&content).unwrap();
+
+        let dir = std::env::temp_dir().join(format!(
+            "chmparse-extract-{}-{}",
+            std::process::id(),
+            std::path::Path::new(file).file_stem().unwrap().to_string_lossy()
+        ));
+        chm_file.extract_all(&dir).unwrap();
+
+        for entry in chm_file.entries().map(Result::unwrap) {
+            if !entry.name.starts_with('/') || entry.name.ends_with('/') {
+                continue;
+            }
+
+            let extracted = std::fs::read(dir.join(&entry.name[1..])).unwrap();
+            assert_eq!(extracted, &*chm_file.read_file(entry.name).unwrap());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn it_reads_the_system_metadata() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let content = std::fs::read(file).unwrap();
+        let chm_file = ChmFile::load(&content).unwrap();
+
+        let system = chm_file.system().unwrap();
+        assert!(system.lcid.is_some());
+        assert!(system.timestamp.is_some());
+        assert!(system
+            .compiler_version
+            .unwrap()
+            .starts_with("HHA Version"));
+        assert!(!system.compiled_file.unwrap().is_empty());
+    }
+}
+
+#[test]
+fn it_reads_the_topic_table() {
+    for file in TEST_FILES {
+        println!("File: {}", file);
+        let content = std::fs::read(file).unwrap();
+        let chm_file = ChmFile::load(&content).unwrap();
+
+        let table = chm_file.topic_table().unwrap();
+        assert!(!table.is_empty());
+
+        for (number, topic) in table.iter() {
+            if let Some(local) = &topic.local {
+                let (found, _) = table.find(local).unwrap();
+                assert_eq!(table[found].local, topic.local);
+                assert!(found <= number);
+            }
+        }
+    }
+}
+
 use std::io::Write;
 fn to_hex_from(slice: &[u8], chunk_size: usize, mut from: usize) -> String {
     if chunk_size == 0 {